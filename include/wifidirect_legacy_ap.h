@@ -0,0 +1,76 @@
+/* C bindings for wifidirect-legacy-ap, built with the `ffi` feature.
+ * Regenerate with `cbindgen --config cbindgen.toml --output include/wifidirect_legacy_ap.h`. */
+
+#ifndef WIFIDIRECT_LEGACY_AP_H
+#define WIFIDIRECT_LEGACY_AP_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Opaque handle to a running hosted network, returned by `whn_start()`.
+ */
+typedef struct WhnHelper WhnHelper;
+
+/**
+ * Receives each event as its type, e.g. `ClientConnected`, and its description. Both strings are
+ * UTF-8, passed with their length in bytes and also NUL-terminated, and are only valid for the
+ * duration of the call. Every call for a network comes from the same thread.
+ */
+typedef void (*WhnEventCallback)(const char *event_type,
+                                 size_t event_type_len,
+                                 const char *payload,
+                                 size_t payload_len,
+                                 void *userdata);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Starts a hosted network and returns a handle to it, or null if the arguments are invalid or
+ * the network couldn't be created. `callback` is called with every event until `whn_free()`.
+ *
+ * # Safety
+ *
+ * `ssid` and `password` must point to `ssid_len` and `password_len` readable bytes of UTF-8.
+ * `userdata` is passed to `callback` as is, from another thread, and must stay valid until
+ * `whn_free()` returns.
+ */
+WhnHelper *whn_start(const char *ssid,
+                     size_t ssid_len,
+                     const char *password,
+                     size_t password_len,
+                     WhnEventCallback callback,
+                     void *userdata);
+
+/**
+ * Stops the network, which can be started again by freeing the handle and calling
+ * `whn_start()`. Returns 0 on success, -1 for a null handle and 1 if stopping failed.
+ *
+ * # Safety
+ *
+ * `handle` must be null or a handle from `whn_start()` that hasn't been freed.
+ */
+int32_t whn_stop(WhnHelper *handle);
+
+/**
+ * Stops the network if it's running and frees the handle, waiting for the callback thread to
+ * finish so the callback isn't called again. See `WlanHostedNetworkHelper::shutdown()`. Null is
+ * ignored.
+ *
+ * # Safety
+ *
+ * `handle` must be null or a handle from `whn_start()` that hasn't been freed, and must not be
+ * used again. It mustn't be freed from inside the callback.
+ */
+void whn_free(WhnHelper *handle);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* WIFIDIRECT_LEGACY_AP_H */
@@ -0,0 +1,80 @@
+use std::collections::HashSet;
+
+/// Which peers the helper's access list lets connect.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AccessMode {
+    /// Only peers passed to `allow_device()` may connect.
+    AllowList,
+    /// Every peer may connect except those passed to `deny_device()`.
+    #[default]
+    DenyList,
+}
+
+/// The device IDs an operator has allowed or denied, consulted on every connection request.
+#[derive(Debug, Default)]
+pub(crate) struct AccessList {
+    pub(crate) mode: AccessMode,
+    allowed: HashSet<String>,
+    denied: HashSet<String>,
+}
+
+impl AccessList {
+    pub(crate) fn new(mode: AccessMode) -> Self {
+        AccessList {
+            mode,
+            ..Default::default()
+        }
+    }
+
+    /// Allows the device, taking it off the deny list if it was on it.
+    pub(crate) fn allow(&mut self, device_id: &str) {
+        self.denied.remove(device_id);
+        self.allowed.insert(device_id.to_string());
+    }
+
+    /// Denies the device, taking it off the allow list if it was on it.
+    pub(crate) fn deny(&mut self, device_id: &str) {
+        self.allowed.remove(device_id);
+        self.denied.insert(device_id.to_string());
+    }
+
+    /// Whether the device may connect under the current mode.
+    pub(crate) fn permits(&self, device_id: &str) -> bool {
+        match self.mode {
+            AccessMode::AllowList => self.allowed.contains(device_id),
+            AccessMode::DenyList => !self.denied.contains(device_id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AccessList, AccessMode};
+
+    #[test]
+    fn lists_follow_the_mode() {
+        let mut list = AccessList::new(AccessMode::DenyList);
+        assert!(list.permits("WiFiDirect#02:8b:21:42:ac:36"));
+        list.deny("WiFiDirect#02:8b:21:42:ac:36");
+        assert!(!list.permits("WiFiDirect#02:8b:21:42:ac:36"));
+        assert!(list.permits("WiFiDirect#5e:11:07:9a:03:c2"));
+
+        list.mode = AccessMode::AllowList;
+        assert!(!list.permits("WiFiDirect#5e:11:07:9a:03:c2"));
+        list.allow("WiFiDirect#5e:11:07:9a:03:c2");
+        assert!(list.permits("WiFiDirect#5e:11:07:9a:03:c2"));
+        assert!(!list.permits("WiFiDirect#02:8b:21:42:ac:36"));
+    }
+
+    #[test]
+    fn allowing_undoes_denying() {
+        let mut list = AccessList::new(AccessMode::DenyList);
+        list.deny("WiFiDirect#02:8b:21:42:ac:36");
+        list.allow("WiFiDirect#02:8b:21:42:ac:36");
+        assert!(list.permits("WiFiDirect#02:8b:21:42:ac:36"));
+        list.mode = AccessMode::AllowList;
+        list.deny("WiFiDirect#02:8b:21:42:ac:36");
+        assert!(!list.permits("WiFiDirect#02:8b:21:42:ac:36"));
+    }
+}
@@ -0,0 +1,360 @@
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::sync;
+use crate::{EventSink, HostedNetworkEvent};
+
+// How long `OverflowPolicy::Block` waits for room before dropping the event anyway, so a stuck
+// consumer can't hang a Windows Runtime callback thread.
+const BLOCK_LIMIT: Duration = Duration::from_secs(1);
+
+/// What a bounded event buffer does with a new event when it is full. Set with
+/// `WlanHostedNetworkBuilder::event_buffer()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OverflowPolicy {
+    /// Wait up to a second for the consumer to make room, then drop the new event.
+    Block,
+    /// Make room by dropping the oldest buffered event.
+    DropOldest,
+    /// Drop the new event.
+    DropNewest,
+}
+
+struct Queue {
+    // each with when it was emitted, which the sink is given on delivery
+    events: VecDeque<(SystemTime, HostedNetworkEvent)>,
+    // dropped since the consumer was last told
+    dropped: usize,
+    closed: bool,
+}
+
+struct Buffer {
+    queue: Mutex<Queue>,
+    // signaled when an event is queued or taken, or the buffer is closed
+    changed: Condvar,
+}
+
+/// Queues events for a worker thread that hands them to the real sink, so the callbacks never
+/// wait on the consumer for longer than the overflow policy allows.
+pub(crate) struct BufferedSink {
+    buffer: Arc<Buffer>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    worker: Mutex<Option<thread::JoinHandle<()>>>,
+}
+
+impl BufferedSink {
+    /// Starts forwarding to `sink` from a thread named `thread_name`, failing if the thread can't
+    /// be started.
+    pub(crate) fn new(
+        capacity: usize,
+        policy: OverflowPolicy,
+        sink: Arc<dyn EventSink>,
+        thread_name: String,
+    ) -> io::Result<Self> {
+        let buffer = Arc::new(Buffer {
+            queue: Mutex::new(Queue {
+                events: VecDeque::new(),
+                dropped: 0,
+                closed: false,
+            }),
+            changed: Condvar::new(),
+        });
+        let worker_buffer = buffer.clone();
+        let worker = sync::spawn(thread_name, move || forward(&worker_buffer, &*sink))?;
+        Ok(BufferedSink {
+            buffer,
+            capacity: capacity.max(1),
+            policy,
+            worker: Mutex::new(Some(worker)),
+        })
+    }
+
+    /// Stops taking events. The worker delivers whatever is still queued, then exits.
+    pub(crate) fn close(&self) {
+        self.buffer
+            .queue
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .closed = true;
+        self.buffer.changed.notify_all();
+    }
+
+    /// Closes the buffer and waits for the worker to deliver what's left.
+    pub(crate) fn join(&self) {
+        self.close();
+        let worker = self
+            .worker
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(worker) = worker {
+            // a panicking sink has already been reported by the panic hook
+            let _ = worker.join();
+        }
+    }
+}
+
+impl EventSink for BufferedSink {
+    fn emit(&self, event: HostedNetworkEvent) {
+        self.emit_at(event, SystemTime::now())
+    }
+
+    fn emit_at(&self, event: HostedNetworkEvent, at: SystemTime) {
+        let mut queue = self
+            .buffer
+            .queue
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if queue.closed {
+            debug!("Event buffer is closed, dropping {}", event);
+            return;
+        }
+        if queue.events.len() >= self.capacity {
+            match self.policy {
+                OverflowPolicy::Block => {
+                    queue = self
+                        .buffer
+                        .changed
+                        .wait_timeout_while(queue, BLOCK_LIMIT, |queue| {
+                            queue.events.len() >= self.capacity && !queue.closed
+                        })
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0;
+                    if queue.closed {
+                        debug!("Event buffer is closed, dropping {}", event);
+                        return;
+                    }
+                    if queue.events.len() >= self.capacity {
+                        queue.dropped += 1;
+                        return;
+                    }
+                }
+                OverflowPolicy::DropOldest => {
+                    queue.events.pop_front();
+                    queue.dropped += 1;
+                }
+                OverflowPolicy::DropNewest => {
+                    queue.dropped += 1;
+                    return;
+                }
+            }
+        }
+        queue.events.push_back((at, event));
+        self.buffer.changed.notify_all();
+    }
+}
+
+impl Drop for BufferedSink {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// Holds events for `WlanHostedNetworkHelper::poll_events()`, dropping the oldest beyond
+/// `capacity`.
+pub(crate) struct PollQueue {
+    queue: Mutex<Queue>,
+    capacity: usize,
+}
+
+impl PollQueue {
+    pub(crate) fn new(capacity: usize) -> Self {
+        PollQueue {
+            queue: Mutex::new(Queue {
+                events: VecDeque::new(),
+                dropped: 0,
+                closed: false,
+            }),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub(crate) fn push(&self, event: HostedNetworkEvent) {
+        let mut queue = self.queue.lock().unwrap_or_else(PoisonError::into_inner);
+        if queue.events.len() >= self.capacity {
+            queue.events.pop_front();
+            queue.dropped += 1;
+        }
+        queue.events.push_back((SystemTime::now(), event));
+    }
+
+    /// Takes every queued event, led by `EventsDropped` if any were lost since the last call.
+    pub(crate) fn drain(&self) -> Vec<HostedNetworkEvent> {
+        let mut queue = self.queue.lock().unwrap_or_else(PoisonError::into_inner);
+        let dropped = std::mem::take(&mut queue.dropped);
+        let mut events = Vec::with_capacity(queue.events.len() + 1);
+        if dropped > 0 {
+            events.push(HostedNetworkEvent::EventsDropped { count: dropped });
+        }
+        events.extend(queue.events.drain(..).map(|(_, event)| event));
+        events
+    }
+}
+
+// Runs on the worker thread. A drop is reported just before the next event handed over.
+fn forward(buffer: &Buffer, sink: &dyn EventSink) {
+    loop {
+        let queue = buffer.queue.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut queue = buffer
+            .changed
+            .wait_while(queue, |queue| {
+                queue.events.is_empty() && queue.dropped == 0 && !queue.closed
+            })
+            .unwrap_or_else(PoisonError::into_inner);
+        if queue.events.is_empty() && queue.dropped == 0 {
+            return;
+        }
+        let dropped = std::mem::take(&mut queue.dropped);
+        let event = queue.events.pop_front();
+        drop(queue);
+        buffer.changed.notify_all();
+        if dropped > 0 {
+            sink.emit(HostedNetworkEvent::EventsDropped { count: dropped });
+        }
+        if let Some((at, event)) = event {
+            sink.emit_at(event, at);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BufferedSink, OverflowPolicy, PollQueue};
+    use crate::sink::TimedSender;
+    use crate::{EventSink, HostedNetworkEvent, TimedEvent};
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, SystemTime};
+
+    // Holds the consumer up on the first event, overfills a buffer of two, then lets it go.
+    fn overflow(policy: OverflowPolicy) -> Vec<HostedNetworkEvent> {
+        let gate = Arc::new(Mutex::new(()));
+        let held = gate.lock().unwrap();
+        let (tx, rx) = mpsc::channel();
+        let consumer_gate = gate.clone();
+        let buffered = BufferedSink::new(
+            2,
+            policy,
+            Arc::new(move |event| {
+                tx.send(event).unwrap();
+                drop(consumer_gate.lock().unwrap());
+            }),
+            "test-events".to_string(),
+        )
+        .unwrap();
+        buffered.emit(HostedNetworkEvent::Retrying { attempt: 1 });
+        assert_eq!(
+            rx.recv().unwrap(),
+            HostedNetworkEvent::Retrying { attempt: 1 }
+        );
+        for attempt in 2..=5 {
+            buffered.emit(HostedNetworkEvent::Retrying { attempt });
+        }
+        drop(held);
+        drop(buffered);
+        rx.iter().collect()
+    }
+
+    #[test]
+    fn buffered_events_keep_their_time() {
+        let (tx, rx) = mpsc::channel::<TimedEvent>();
+        let buffered = BufferedSink::new(
+            2,
+            OverflowPolicy::Block,
+            Arc::new(TimedSender(tx)),
+            "test-events".to_string(),
+        )
+        .unwrap();
+        let before = SystemTime::now();
+        buffered.emit(HostedNetworkEvent::Created);
+        thread::sleep(Duration::from_millis(20));
+        buffered.join();
+        let timed = rx.recv().unwrap();
+        assert_eq!(timed.event, HostedNetworkEvent::Created);
+        assert!(timed.at >= before && timed.at < before + Duration::from_millis(20));
+    }
+
+    #[test]
+    fn poll_queue_drops_oldest() {
+        let queue = PollQueue::new(2);
+        for attempt in 1..=3 {
+            queue.push(HostedNetworkEvent::Retrying { attempt });
+        }
+        assert_eq!(
+            queue.drain(),
+            vec![
+                HostedNetworkEvent::EventsDropped { count: 1 },
+                HostedNetworkEvent::Retrying { attempt: 2 },
+                HostedNetworkEvent::Retrying { attempt: 3 },
+            ]
+        );
+        assert!(queue.drain().is_empty());
+    }
+
+    #[test]
+    fn drop_newest_keeps_the_first_events() {
+        assert_eq!(
+            overflow(OverflowPolicy::DropNewest),
+            vec![
+                HostedNetworkEvent::EventsDropped { count: 2 },
+                HostedNetworkEvent::Retrying { attempt: 2 },
+                HostedNetworkEvent::Retrying { attempt: 3 },
+            ]
+        );
+    }
+
+    #[test]
+    fn drop_oldest_keeps_the_latest_events() {
+        assert_eq!(
+            overflow(OverflowPolicy::DropOldest),
+            vec![
+                HostedNetworkEvent::EventsDropped { count: 2 },
+                HostedNetworkEvent::Retrying { attempt: 4 },
+                HostedNetworkEvent::Retrying { attempt: 5 },
+            ]
+        );
+    }
+
+    #[test]
+    fn closing_drops_a_blocked_event() {
+        let gate = Arc::new(Mutex::new(()));
+        let held = gate.lock().unwrap();
+        let (tx, rx) = mpsc::channel();
+        let consumer_gate = gate.clone();
+        let buffered = Arc::new(
+            BufferedSink::new(
+                1,
+                OverflowPolicy::Block,
+                Arc::new(move |event| {
+                    tx.send(event).unwrap();
+                    drop(consumer_gate.lock().unwrap());
+                }),
+                "test-events".to_string(),
+            )
+            .unwrap(),
+        );
+        buffered.emit(HostedNetworkEvent::Retrying { attempt: 1 });
+        assert_eq!(
+            rx.recv().unwrap(),
+            HostedNetworkEvent::Retrying { attempt: 1 }
+        );
+        buffered.emit(HostedNetworkEvent::Retrying { attempt: 2 });
+        let blocked = buffered.clone();
+        let emitter =
+            thread::spawn(move || blocked.emit(HostedNetworkEvent::Retrying { attempt: 3 }));
+        thread::sleep(Duration::from_millis(20));
+        buffered.close();
+        emitter.join().unwrap();
+        drop(held);
+        buffered.join();
+        assert_eq!(
+            rx.try_iter().collect::<Vec<_>>(),
+            vec![HostedNetworkEvent::Retrying { attempt: 2 }]
+        );
+    }
+}
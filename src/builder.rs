@@ -0,0 +1,738 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::access::{AccessList, AccessMode};
+use crate::buffer::{BufferedSink, OverflowPolicy, PollQueue};
+use crate::client::Clients;
+use crate::credentials::Credentials;
+use crate::history::ConnectionHistory;
+use crate::idle::spawn_idle_worker;
+use crate::passphrase::random_passphrase;
+use crate::request::{ConnectionCallback, ConnectionDecision, ConnectionRequestInfo};
+use crate::retry::{spawn_restart_worker, RetryPolicy};
+use crate::scan;
+use crate::sink::{FanOut, TimedSender};
+use crate::status::StatusCache;
+use crate::sync;
+use crate::validate::{
+    validate_group_owner_intent, validate_information_elements, validate_passphrase, validate_ssid,
+    DIRECT_SSID_PREFIX,
+};
+use crate::{
+    start, ConnectionFilter, EventSink, HostedNetworkError, HostedNetworkEvent, Plumbing, Result,
+    Shared, StartNotifier, TimedEvent, WlanHostedNetworkHelper,
+};
+
+// length of the passphrase generated by `WlanHostedNetworkBuilder::random_passphrase()`
+const RANDOM_PASSPHRASE_CHARS: usize = 20;
+
+// how often `build_cancellable()` looks at its flag while waiting for the network to start
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Configures and starts a `WlanHostedNetworkHelper`.
+///
+/// ```no_run
+/// use std::sync::mpsc;
+/// use wifidirect_legacy_ap::WlanHostedNetworkBuilder;
+///
+/// let (message_tx, _message_rx) = mpsc::channel();
+/// let hotspot = WlanHostedNetworkBuilder::new()
+///     .ssid("WiFiDirectTestNetwork")
+///     .passphrase("TestingThisLibrary")
+///     .event_sender(message_tx)
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Clone)]
+pub struct WlanHostedNetworkBuilder {
+    ssid: Option<String>,
+    passphrase: Option<String>,
+    settings: AdvertisementSettings,
+    group_owner_intent: Option<u8>,
+    auto_restart: Option<RetryPolicy>,
+    idle_timeout: Option<Duration>,
+    connection_filter: Option<ConnectionFilter>,
+    on_connection_request: Option<ConnectionCallback>,
+    access_mode: AccessMode,
+    track_connections: bool,
+    reconnect_window: Option<Duration>,
+    max_clients: Option<usize>,
+    event_sinks: Vec<Arc<dyn EventSink>>,
+    event_buffer: Option<(usize, OverflowPolicy)>,
+    connection_history: Option<usize>,
+    warn_on_ssid_collision: bool,
+    reject_ssid_collision: bool,
+    warn_without_direct_prefix: bool,
+    thread_name_prefix: String,
+    // set by `WlanHostedNetworkHelper::new_buffered`, which also makes it the event sink
+    pub(crate) poll_queue: Option<Arc<PollQueue>>,
+    success_sender: Option<Sender<bool>>,
+}
+
+impl WlanHostedNetworkBuilder {
+    pub fn new() -> Self {
+        WlanHostedNetworkBuilder {
+            ssid: None,
+            passphrase: None,
+            settings: AdvertisementSettings {
+                autonomous_group_owner: true,
+                discoverability: None,
+                information_elements: Vec::new(),
+                username: None,
+            },
+            group_owner_intent: None,
+            auto_restart: None,
+            idle_timeout: None,
+            connection_filter: None,
+            on_connection_request: None,
+            access_mode: AccessMode::DenyList,
+            track_connections: true,
+            reconnect_window: None,
+            max_clients: None,
+            event_sinks: Vec::new(),
+            event_buffer: None,
+            connection_history: None,
+            warn_on_ssid_collision: false,
+            reject_ssid_collision: false,
+            warn_without_direct_prefix: false,
+            thread_name_prefix: sync::DEFAULT_THREAD_NAME_PREFIX.to_string(),
+            poll_queue: None,
+            success_sender: None,
+        }
+    }
+
+    /// Name of the network. Optional: without one, Windows makes one up when the network starts,
+    /// in the `DIRECT-xy-<PC name>` style, and `WlanHostedNetworkHelper::ssid()` reports it.
+    ///
+    /// This is what legacy clients see. Wi-Fi Direct peers show the PC's device name instead,
+    /// which WinRT doesn't let an app set.
+    pub fn ssid(&mut self, ssid: &str) -> &mut Self {
+        self.ssid = Some(ssid.to_string());
+        self
+    }
+
+    /// Names the network `DIRECT-<suffix>`, following the Wi-Fi Direct convention, instead of
+    /// taking the whole SSID from `ssid()`. The spec names every group that way, and some peers,
+    /// notably Android's Wi-Fi Direct stack and printers, only treat a network as a Wi-Fi Direct
+    /// group, or only list it among Wi-Fi Direct devices, if its SSID has the prefix. The spec's
+    /// own form is `DIRECT-xy-<name>` with two random characters, which the suffix can follow.
+    /// The prefix counts towards the 32-byte limit `build()` checks, leaving 25 for `suffix`.
+    pub fn direct_ssid(&mut self, suffix: &str) -> &mut Self {
+        self.ssid = Some(format!("{}{}", DIRECT_SSID_PREFIX, suffix));
+        self
+    }
+
+    /// Sends `SsidPrefixWarning` from `build()` if the SSID given to `ssid()` doesn't start with
+    /// `DIRECT-`, for apps whose peers expect the convention; see `direct_ssid()`. The network
+    /// starts either way, and legacy clients don't care. Off by default.
+    pub fn warn_without_direct_prefix(&mut self, enabled: bool) -> &mut Self {
+        self.warn_without_direct_prefix = enabled;
+        self
+    }
+
+    /// WPA2 passphrase peers use to join the network. Required: Wi-Fi Direct groups are always
+    /// WPA2-Personal, so there is no open mode, and an empty passphrase is rejected by `build()`.
+    pub fn passphrase(&mut self, passphrase: &str) -> &mut Self {
+        self.passphrase = Some(passphrase.to_string());
+        self
+    }
+
+    /// Uses a freshly generated random passphrase instead of one passed to `passphrase()`.
+    pub fn random_passphrase(&mut self) -> &mut Self {
+        self.passphrase = Some(random_passphrase(RANDOM_PASSPHRASE_CHARS));
+        self
+    }
+
+    /// Whether the PC becomes group owner without negotiating with peers. Defaults to `true`.
+    ///
+    /// When `true`, starting forms a group with the PC as its owner straight away, so the legacy
+    /// access point is up as soon as `Started` arrives. When `false`, Windows only advertises and
+    /// waits for a Wi-Fi Direct peer to negotiate group ownership; the group, and with it the
+    /// legacy access point, may not exist until one does, and a peer can win the negotiation.
+    /// Legacy clients can't negotiate, so leave this on unless Wi-Fi Direct peers start the
+    /// connection.
+    pub fn autonomous_group_owner(&mut self, enabled: bool) -> &mut Self {
+        self.settings.autonomous_group_owner = enabled;
+        self
+    }
+
+    /// How strongly the PC wants to be group owner when a Wi-Fi Direct peer negotiates with it,
+    /// from 0 to 15, where 15 insists. Only meaningful with `autonomous_group_owner(false)`:
+    /// `build()` fails with `HostedNetworkError::InvalidGroupOwnerIntent` if autonomous group
+    /// ownership is still on, or if `intent` is over 15. Left to Windows unless set.
+    ///
+    /// The intent goes into the connection parameters for each accepted peer, so it needs
+    /// `track_connections()`, which is on by default. It has no bearing on the legacy settings:
+    /// legacy clients can't negotiate and only see the access point while the PC is group owner,
+    /// so a low intent can leave them with nothing to join.
+    pub fn group_owner_intent(&mut self, intent: u8) -> &mut Self {
+        self.group_owner_intent = Some(intent);
+        self
+    }
+
+    /// Sets the user name on the `PasswordCredential` that carries the passphrase. A legacy
+    /// access point is WPA2-Personal, which authenticates with the passphrase alone, so Windows
+    /// doesn't send the user name to clients or check it, and it doesn't change how peers join.
+    /// It's here for completeness, for setups that record it. Unset by default.
+    pub fn username(&mut self, username: &str) -> &mut Self {
+        self.settings.username = Some(username.to_string());
+        self
+    }
+
+    /// How discoverable the PC is to Wi-Fi Direct peers looking for it. `Intensive` makes it
+    /// show up faster by keeping the radio listening more of the time, which costs noticeably
+    /// more power on battery. Left at the Windows default unless set.
+    pub fn discoverability(&mut self, discoverability: Discoverability) -> &mut Self {
+        self.settings.discoverability = Some(discoverability);
+        self
+    }
+
+    /// Adds a vendor-specific information element to the advertisement, identified by the
+    /// vendor's `oui` and an `oui_type` of the vendor's choosing, so Wi-Fi Direct peers can tell
+    /// what the PC offers before connecting. Can be called more than once.
+    ///
+    /// `oui_type` is its own argument rather than the first byte of `data` because WinRT keeps it
+    /// apart: `WiFiDirectInformationElement` has `Oui`, `OuiType` and `Value`, and a peer reading
+    /// the elements gets them back the same way, so one vendor can tell its element types apart
+    /// without parsing the payload.
+    ///
+    /// An 802.11 element holds at most 255 bytes, four of which are the OUI and type, so `data`
+    /// can be up to 251 bytes. Windows adds its own elements to the same frames and aborts the
+    /// network if they no longer fit, so all the elements together, with two header bytes each,
+    /// can take up at most 512 bytes. `build()` fails with
+    /// `HostedNetworkError::InvalidInformationElement` past either limit.
+    ///
+    /// Only Wi-Fi Direct peers see the elements: they are in the advertisement's beacons and probe
+    /// responses, and a Windows peer reads them with
+    /// `WiFiDirectInformationElement::CreateFromDeviceInformation()` on the `DeviceInformation` it
+    /// discovered. Legacy clients joining the access point don't surface them.
+    pub fn add_information_element(
+        &mut self,
+        oui: [u8; 3],
+        oui_type: u8,
+        data: &[u8],
+    ) -> &mut Self {
+        self.settings.information_elements.push(InformationElement {
+            oui,
+            oui_type,
+            data: data.to_vec(),
+        });
+        self
+    }
+
+    /// Restarts the network according to `policy` when Windows aborts it with a transient error
+    /// such as `RadioNotAvailable` or `ResourceInUse`, or aborts it for any reason once it has
+    /// started. Off by default.
+    ///
+    /// The second case covers sleep and resume: the adapter resets and Windows aborts the
+    /// publisher, which is restarted with a `PowerResumeRestart` event. Windows power
+    /// notifications aren't listened to, so recovery only starts once the abort is reported, and
+    /// a network that is still stopped when `policy` runs out stays down.
+    pub fn auto_restart(&mut self, policy: RetryPolicy) -> &mut Self {
+        self.auto_restart = Some(policy);
+        self
+    }
+
+    /// Stops the network once it has had no connected clients for `timeout`, sending
+    /// `IdleStopped`. The clock starts with the network and restarts whenever a client connects.
+    /// Off by default.
+    pub fn idle_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Called with the device ID of each peer that asks to connect; returning `false` turns it
+    /// away with a `ConnectionRejected` event instead of a `ClientConnected` one.
+    ///
+    /// This is an application-level gate only. Windows associates legacy clients at the Wi-Fi
+    /// layer before the request reaches this crate, so a rejected peer that knows the passphrase
+    /// may still get an 802.11 link; it just never becomes a connected client here.
+    pub fn connection_filter<F>(&mut self, filter: F) -> &mut Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.connection_filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// Called with what's known about each peer that asks to connect, after any
+    /// `connection_filter()`; returning `ConnectionDecision::Reject` turns it away with a
+    /// `ConnectionRejected` event. Unlike the filter it may block, e.g. to look the peer up in a
+    /// database: it runs on a thread of its own, and a peer whose decision takes longer than
+    /// `CONNECTION_DECISION_TIMEOUT`, or whose callback panics, is rejected. The same caveat about
+    /// the 802.11 link applies.
+    pub fn on_connection_request<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: Fn(ConnectionRequestInfo) -> ConnectionDecision + Send + Sync + 'static,
+    {
+        self.on_connection_request = Some(Arc::new(callback));
+        self
+    }
+
+    /// How the helper's access list starts out, `AccessMode::DenyList` by default. Starting in
+    /// `AccessMode::AllowList` turns every peer away until `allow_device()` is called for it.
+    pub fn access_mode(&mut self, mode: AccessMode) -> &mut Self {
+        self.access_mode = mode;
+        self
+    }
+
+    /// Whether to follow peers once their connection is accepted. On by default. Turning it off
+    /// saves resolving a `WiFiDirectDevice` for every peer when the network only needs to be
+    /// joinable: connection requests are still accepted and reported with `ConnectionRequested`,
+    /// but no `ClientConnected`, `ClientReady` or `ClientDisconnected` events are sent,
+    /// `connected_clients()` is always empty, and the other per-client methods report
+    /// `UnknownClient`. An `idle_timeout()` then counts the network as idle the whole time.
+    pub fn track_connections(&mut self, track: bool) -> &mut Self {
+        self.track_connections = track;
+        self
+    }
+
+    /// Treats a peer that connects again within `window` of disconnecting as the same connection:
+    /// it's reported with `ClientReconnected` rather than `ClientConnected`, and
+    /// `client_uptime()` keeps counting from its first connection. Its `ClientDisconnected` is
+    /// still sent. Off by default, so every connection is a new one.
+    pub fn reconnect_window(&mut self, window: Duration) -> &mut Self {
+        self.reconnect_window = Some(window);
+        self
+    }
+
+    /// Caps how many peers can be connected at once. When the `limit`th connects,
+    /// `MaxClientsReached` is sent, and further peers are turned away with `ConnectionRejected`
+    /// until one leaves, so an app can show the network as full rather than have joins fail
+    /// silently. Uncapped by default.
+    ///
+    /// Windows has its own cap, commonly eight clients for a legacy access point though it's up
+    /// to the driver, and doesn't report it or say when it's been hit. Setting `limit` at or
+    /// below it makes the cap visible. Needs `track_connections()`, which is on by default, to
+    /// count the peers.
+    pub fn max_clients(&mut self, limit: usize) -> &mut Self {
+        self.max_clients = Some(limit);
+        self
+    }
+
+    /// Channel that will receive status updates from the Windows Runtime. This or `event_sink()`
+    /// is required.
+    pub fn event_sender(&mut self, tx: Sender<HostedNetworkEvent>) -> &mut Self {
+        self.event_sink(tx)
+    }
+
+    /// Like `event_sender()`, but each event comes with the time it was emitted. Replaces any
+    /// `event_sender()` or `event_sink()`.
+    pub fn timed_event_sender(&mut self, tx: Sender<TimedEvent>) -> &mut Self {
+        self.event_sink(TimedSender(tx))
+    }
+
+    /// Where status updates from the Windows Runtime go, for callers not using a
+    /// `std::sync::mpsc` channel. Replaces any `event_sender()` or sinks added before. A sink that
+    /// panics is logged and skipped for that event, and still gets the ones after it.
+    pub fn event_sink<S: EventSink + 'static>(&mut self, sink: S) -> &mut Self {
+        self.event_sinks = vec![Arc::new(sink)];
+        self
+    }
+
+    /// Adds another place for status updates to go, alongside any `event_sender()` or
+    /// `event_sink()`, e.g. a logging closure next to a UI channel. Each event goes to every sink
+    /// in the order they were added. A sink that panics is skipped for that event and the rest
+    /// still get it, but a slow one holds up those after it, so put slow sinks last or add an
+    /// `event_buffer()` to take delivery off the Windows Runtime callbacks.
+    pub fn add_event_sink<S: EventSink + 'static>(&mut self, sink: S) -> &mut Self {
+        self.event_sinks.push(Arc::new(sink));
+        self
+    }
+
+    /// Buffers up to `capacity` events between the Windows Runtime callbacks and the event sink,
+    /// handing them over from a separate thread so a slow consumer doesn't hold the callbacks
+    /// up. When the buffer is full, `policy` decides what gives, and the sink is told how many
+    /// events it missed with `EventsDropped`. Unbuffered by default.
+    pub fn event_buffer(&mut self, capacity: usize, policy: OverflowPolicy) -> &mut Self {
+        self.event_buffer = Some((capacity, policy));
+        self
+    }
+
+    /// What the crate's threads are named after, e.g. `wifidirect-ap-events` for the
+    /// `event_buffer()` thread, so they can be told apart in debuggers and crash dumps. Windows
+    /// shows the name as the thread's description. Linux truncates thread names to 15 bytes, so
+    /// keep `prefix` short there. Defaults to `wifidirect-ap`.
+    pub fn thread_name_prefix(&mut self, prefix: &str) -> &mut Self {
+        self.thread_name_prefix = prefix.to_string();
+        self
+    }
+
+    /// Keeps the last `limit` connects, reconnects, disconnects and rejections in memory, for
+    /// `WlanHostedNetworkHelper::connection_history()`. Off by default.
+    pub fn connection_history(&mut self, limit: usize) -> &mut Self {
+        self.connection_history = Some(limit);
+        self
+    }
+
+    /// Scans for access points already broadcasting the SSID before the network first starts,
+    /// sending `SsidCollisionWarning` if there are any, since clients may join one of them
+    /// instead. The network starts either way. Scanning holds `build()` up for a few seconds,
+    /// and a scan that fails is logged and skipped. Off by default.
+    pub fn warn_on_ssid_collision(&mut self, enabled: bool) -> &mut Self {
+        self.warn_on_ssid_collision = enabled;
+        self
+    }
+
+    /// Like `warn_on_ssid_collision()`, but `build()` fails with
+    /// `HostedNetworkError::SsidCollision` instead of starting, as it does if the scan fails.
+    /// Off by default.
+    pub fn reject_ssid_collision(&mut self, enabled: bool) -> &mut Self {
+        self.reject_ssid_collision = enabled;
+        self
+    }
+
+    /// Channel that will receive one `bool` when the network starts or fails to start.
+    pub fn success_sender(&mut self, tx: Sender<bool>) -> &mut Self {
+        self.success_sender = Some(tx);
+        self
+    }
+
+    /// Checks the SSID, passphrase and options the way `build()` does, without starting anything,
+    /// so a configuration can be tested in CI or on a machine with Wi-Fi off. It makes no Windows
+    /// Runtime calls, so it works the same on non-Windows targets, with or without the `mock`
+    /// feature, and it doesn't need an event sink. The SSID collision scan is skipped, as it needs
+    /// the radio, so `reject_ssid_collision()` can still fail `build()` on a valid configuration.
+    pub fn validate(&self) -> Result<()> {
+        let passphrase = self
+            .passphrase
+            .as_deref()
+            .ok_or(HostedNetworkError::missing_configuration("passphrase"))?;
+        if let Some(ssid) = &self.ssid {
+            validate_ssid(ssid)?;
+        }
+        validate_passphrase(passphrase)?;
+        validate_information_elements(&self.settings.information_elements)?;
+        if let Some(intent) = self.group_owner_intent {
+            validate_group_owner_intent(intent, self.settings.autonomous_group_owner)?;
+        }
+        Ok(())
+    }
+
+    /// Starts the hosted network. The SSID and passphrase are checked against the 802.11 and WPA2
+    /// length limits first, so bad values are rejected here rather than aborting the network later.
+    pub fn build(&self) -> Result<WlanHostedNetworkHelper> {
+        let on_start = self.success_sender.clone().map(|success_tx| {
+            Box::new(move |result: Result<()>| {
+                let _ = success_tx.send(result.is_ok());
+            }) as StartNotifier
+        });
+        self.build_with(on_start)
+    }
+
+    /// Starts the hosted network like `build()`, then waits up to `timeout` for Windows to report
+    /// it as started. If the publisher aborts instead, e.g. because the radio is off, its error is
+    /// returned here rather than only arriving later as an `Aborted` event, and the network is
+    /// torn down. Returns `HostedNetworkError::Timeout` if neither happens in time.
+    pub fn build_checked(&self, timeout: Duration) -> Result<WlanHostedNetworkHelper> {
+        self.build_and_wait(Some(timeout), None)
+    }
+
+    /// Starts the hosted network like `build_checked()`, but instead of a timeout, waits until
+    /// `cancel` is set, for example from a GUI's cancel button. Once it is, the half-started
+    /// network is stopped, its handlers are removed, and `HostedNetworkError::Canceled` is
+    /// returned. If `cancel` is already set, nothing is started at all.
+    pub fn build_cancellable(&self, cancel: &AtomicBool) -> Result<WlanHostedNetworkHelper> {
+        self.build_and_wait(None, Some(cancel))
+    }
+
+    fn build_and_wait(
+        &self,
+        timeout: Option<Duration>,
+        cancel: Option<&AtomicBool>,
+    ) -> Result<WlanHostedNetworkHelper> {
+        let canceled = || cancel.is_some_and(|cancel| cancel.load(Ordering::SeqCst));
+        if canceled() {
+            return Err(HostedNetworkError::Canceled);
+        }
+        // a timeout too long to represent is as good as none
+        let deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+        let outcome = Arc::new((Mutex::new(None), Condvar::new()));
+        let notify = outcome.clone();
+        let success_tx = self.success_sender.clone();
+        let helper = self.build_with(Some(Box::new(move |result: Result<()>| {
+            if let Some(success_tx) = &success_tx {
+                let _ = success_tx.send(result.is_ok());
+            }
+            let (lock, settled) = &*notify;
+            // only the first start counts, not an auto-restart after it
+            lock.lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .get_or_insert(result);
+            settled.notify_all();
+        })))?;
+        let (lock, settled) = &*outcome;
+        let mut result = sync::lock(lock, "start outcome");
+        loop {
+            match result.take() {
+                Some(Ok(())) => return Ok(helper),
+                Some(Err(error)) => return Err(error),
+                None => {}
+            }
+            let remaining = deadline.map(|deadline| {
+                deadline
+                    .checked_duration_since(Instant::now())
+                    .unwrap_or_default()
+            });
+            let gave_up = if canceled() {
+                Some(HostedNetworkError::Canceled)
+            } else if remaining.is_some_and(|remaining| remaining.is_zero()) {
+                Some(HostedNetworkError::Timeout)
+            } else {
+                None
+            };
+            if let Some(error) = gave_up {
+                drop(result);
+                helper.cancel_start();
+                return Err(error);
+            }
+            let mut wait = remaining.unwrap_or(CANCEL_POLL_INTERVAL);
+            if cancel.is_some() {
+                wait = wait.min(CANCEL_POLL_INTERVAL);
+            }
+            result = settled
+                .wait_timeout(result, wait)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+    }
+
+    pub(crate) fn build_with(
+        &self,
+        on_start: Option<StartNotifier>,
+    ) -> Result<WlanHostedNetworkHelper> {
+        // empty means Windows picks it
+        let ssid = self.ssid.as_deref().unwrap_or("");
+        let passphrase = self
+            .passphrase
+            .as_deref()
+            .ok_or(HostedNetworkError::missing_configuration("passphrase"))?;
+        self.validate()?;
+        let events: Arc<dyn EventSink> = match self.event_sinks.as_slice() {
+            [] => return Err(HostedNetworkError::missing_configuration("event_sender")),
+            // even a lone sink is wrapped, so its panics are caught before they reach WinRT
+            sinks => Arc::new(FanOut(sinks.to_vec())),
+        };
+        if self.warn_without_direct_prefix
+            && !ssid.is_empty()
+            && !ssid.starts_with(DIRECT_SSID_PREFIX)
+        {
+            warn!("SSID {} doesn't start with {}", ssid, DIRECT_SSID_PREFIX);
+            events.emit(HostedNetworkEvent::SsidPrefixWarning {
+                ssid: ssid.to_string(),
+            });
+        }
+        if ssid.is_empty() {
+            // nothing to compare against until Windows has chosen one
+        } else if self.reject_ssid_collision {
+            let bssid_count = scan::count_bssids(ssid)?;
+            if bssid_count > 0 {
+                return Err(HostedNetworkError::SsidCollision {
+                    ssid: ssid.to_string(),
+                    bssid_count,
+                });
+            }
+        } else if self.warn_on_ssid_collision {
+            match scan::count_bssids(ssid) {
+                Ok(0) => {}
+                Ok(bssid_count) => events.emit(HostedNetworkEvent::SsidCollisionWarning {
+                    ssid: ssid.to_string(),
+                    bssid_count,
+                }),
+                Err(error) => warn!("Couldn't scan for SSID {}: {}", ssid, error),
+            }
+        }
+        let buffered_sink = self
+            .event_buffer
+            .map(|(capacity, policy)| {
+                BufferedSink::new(
+                    capacity,
+                    policy,
+                    events.clone(),
+                    format!("{}-events", self.thread_name_prefix),
+                )
+                .map(Arc::new)
+            })
+            .transpose()
+            .map_err(HostedNetworkError::thread_spawn_failed)?;
+        let events: Arc<dyn EventSink> = match &buffered_sink {
+            Some(buffered_sink) => buffered_sink.clone(),
+            None => events,
+        };
+        // outside the buffer, so records are stamped when the event happens
+        let history = self
+            .connection_history
+            .map(|limit| Arc::new(ConnectionHistory::new(limit, events.clone())));
+        let events: Arc<dyn EventSink> = match &history {
+            Some(history) => history.clone(),
+            None => events,
+        };
+
+        let (restart_tx, restart_rx) = match self.auto_restart {
+            Some(_) => {
+                let (tx, rx) = mpsc::channel();
+                (Some(tx), Some(rx))
+            }
+            None => (None, None),
+        };
+        let plumbing = Plumbing {
+            events,
+            listener: Arc::new(Mutex::new(None)),
+            clients: Arc::new(Clients::new(self.reconnect_window)),
+            connection_filter: self.connection_filter.clone(),
+            on_connection_request: self.on_connection_request.clone(),
+            access_list: Arc::new(Mutex::new(AccessList::new(self.access_mode))),
+            paused: Arc::new(AtomicBool::new(false)),
+            track_connections: self.track_connections,
+            #[cfg(windows)]
+            group_owner_intent: self.group_owner_intent,
+            max_clients: self.max_clients,
+            restart_tx,
+            shutdown: Arc::new(AtomicBool::new(false)),
+            started_at: Arc::new(Mutex::new(None)),
+            status: Arc::new(Mutex::new(StatusCache::new())),
+            #[cfg(feature = "tracing")]
+            span: tracing::info_span!(
+                "hosted_network",
+                ssid = %ssid,
+                status = tracing::field::Empty
+            ),
+            thread_prefix: self.thread_name_prefix.as_str().into(),
+            #[cfg(feature = "tokio")]
+            stop_reason: Arc::new(tokio::sync::watch::channel(None).0),
+        };
+        let publisher = start(ssid, passphrase, &self.settings, plumbing.clone(), on_start)?;
+        let inner = Arc::new(Shared {
+            publisher: Mutex::new(publisher),
+            plumbing,
+            settings: self.settings.clone(),
+            credentials: Mutex::new(Credentials {
+                ssid: ssid.to_string(),
+                passphrase: passphrase.to_string(),
+            }),
+            stopped: AtomicBool::new(false),
+            poll_queue: self.poll_queue.clone(),
+            workers: Mutex::new(Vec::new()),
+            buffered_sink,
+            history,
+        });
+        // the network is up by now, and dropping `inner` on an early return takes it down again
+        let mut workers = Vec::new();
+        if let (Some(policy), Some(restart_rx)) = (self.auto_restart, restart_rx) {
+            workers.push(
+                spawn_restart_worker(
+                    policy,
+                    restart_rx,
+                    Arc::downgrade(&inner),
+                    inner.plumbing.thread_name("restart"),
+                )
+                .map_err(HostedNetworkError::thread_spawn_failed)?,
+            );
+        }
+        if let Some(timeout) = self.idle_timeout {
+            workers.extend(
+                spawn_idle_worker(timeout, Arc::downgrade(&inner))
+                    .map_err(HostedNetworkError::thread_spawn_failed)?,
+            );
+        }
+        *sync::lock(&inner.workers, "workers") = workers;
+        Ok(WlanHostedNetworkHelper { inner })
+    }
+}
+
+/// Advertisement options kept by the helper so the network can be started again with them.
+#[derive(Clone)]
+pub(crate) struct AdvertisementSettings {
+    pub(crate) autonomous_group_owner: bool,
+    pub(crate) discoverability: Option<Discoverability>,
+    pub(crate) information_elements: Vec<InformationElement>,
+    // set on the passphrase's PasswordCredential, though legacy clients never see it
+    pub(crate) username: Option<String>,
+}
+
+/// A vendor-specific information element added with
+/// `WlanHostedNetworkBuilder::add_information_element()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct InformationElement {
+    pub(crate) oui: [u8; 3],
+    pub(crate) oui_type: u8,
+    pub(crate) data: Vec<u8>,
+}
+
+/// How discoverable the PC is while advertising, mirroring
+/// `WiFiDirectAdvertisementListenStateDiscoverability`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Discoverability {
+    /// Not discoverable by Wi-Fi Direct peers. Legacy clients can still see the SSID.
+    None,
+    /// Discoverable at the normal listen duty cycle.
+    Normal,
+    /// Discoverable more quickly, at the cost of extra power use.
+    Intensive,
+}
+
+impl Default for WlanHostedNetworkBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WlanHostedNetworkBuilder;
+    use crate::HostedNetworkError;
+
+    #[test]
+    fn build_requires_passphrase_and_sender() {
+        let mut builder = WlanHostedNetworkBuilder::new();
+        assert_eq!(
+            builder.build().err(),
+            Some(HostedNetworkError::missing_configuration("passphrase"))
+        );
+        builder.passphrase("TestingThisLibrary");
+        assert_eq!(
+            builder.build().err(),
+            Some(HostedNetworkError::missing_configuration("event_sender"))
+        );
+    }
+
+    #[test]
+    fn a_given_ssid_is_still_validated() {
+        let mut builder = WlanHostedNetworkBuilder::new();
+        builder.ssid("").passphrase("TestingThisLibrary");
+        assert!(matches!(
+            builder.build().err(),
+            Some(HostedNetworkError::InvalidSsid(_))
+        ));
+    }
+
+    #[test]
+    fn validate_checks_without_starting() {
+        let mut builder = WlanHostedNetworkBuilder::new();
+        assert_eq!(
+            builder.validate(),
+            Err(HostedNetworkError::missing_configuration("passphrase"))
+        );
+        builder.passphrase("short");
+        assert!(matches!(
+            builder.validate(),
+            Err(HostedNetworkError::InvalidPassphrase(_))
+        ));
+        // no event sink is needed
+        builder.passphrase("TestingThisLibrary");
+        assert_eq!(builder.validate(), Ok(()));
+        builder.ssid(&"a".repeat(33));
+        assert!(matches!(
+            builder.validate(),
+            Err(HostedNetworkError::InvalidSsid(_))
+        ));
+        builder.ssid("WiFiDirectTestNetwork").group_owner_intent(7);
+        assert!(matches!(
+            builder.validate(),
+            Err(HostedNetworkError::InvalidGroupOwnerIntent(_))
+        ));
+    }
+}
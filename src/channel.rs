@@ -0,0 +1,34 @@
+use std::fmt;
+
+/// Frequency band a Wi-Fi channel lies in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Band {
+    Band2_4GHz,
+    Band5GHz,
+    Unknown,
+}
+
+/// The band and channel number the hosted network is operating on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelInfo {
+    pub band: Band,
+    pub channel: u32,
+}
+
+impl fmt::Display for Band {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Band2_4GHz => write!(f, "2.4 GHz"),
+            Self::Band5GHz => write!(f, "5 GHz"),
+            Self::Unknown => write!(f, "unknown band"),
+        }
+    }
+}
+
+impl fmt::Display for ChannelInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "channel {} ({})", self.channel, self.band)
+    }
+}
@@ -0,0 +1,588 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, Ipv6Addr, SocketAddr, SocketAddrV6};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError, TryLockResult};
+use std::time::{Duration, Instant};
+
+#[cfg(windows)]
+use crate::platform::{
+    self, DeviceInformation, EventRegistrationToken, WiFiDirectConnectionStatus, WiFiDirectDevice,
+};
+use crate::{sync, Guid, Result};
+
+/// Connected clients, shared between the helper and the WinRT callbacks.
+pub(crate) type ClientRegistry = Arc<Clients>;
+
+pub(crate) struct Clients {
+    // HSTRING doesn't implement Hash in this version of windows-rs, so the key is the ID as a String
+    map: Mutex<HashMap<String, ConnectedClient>>,
+    /// Notified whenever a client is added or removed, for callers waiting on the map's lock.
+    pub(crate) changed: Condvar,
+    // clients that disconnected within the reconnect window, by device ID
+    departed: Mutex<HashMap<String, Departure>>,
+    reconnect_window: Option<Duration>,
+}
+
+struct Departure {
+    left_at: Instant,
+    connected_at: Instant,
+}
+
+impl Clients {
+    /// Makes an empty registry. With a `reconnect_window`, a client that comes back within that
+    /// long of disconnecting is recognized by `take_rejoin()`.
+    pub(crate) fn new(reconnect_window: Option<Duration>) -> Self {
+        Clients {
+            map: Mutex::new(HashMap::new()),
+            changed: Condvar::new(),
+            departed: Mutex::new(HashMap::new()),
+            reconnect_window,
+        }
+    }
+
+    /// Remembers that a client disconnected at `now`, having first connected at `connected_at`.
+    pub(crate) fn record_departure(&self, device_id: &str, connected_at: Instant, now: Instant) {
+        let window = match self.reconnect_window {
+            Some(window) => window,
+            None => return,
+        };
+        let mut departed = self.departed.lock().unwrap_or_else(PoisonError::into_inner);
+        departed.retain(|_, departure| now.duration_since(departure.left_at) < window);
+        departed.insert(
+            device_id.to_string(),
+            Departure {
+                left_at: now,
+                connected_at,
+            },
+        );
+    }
+
+    /// If the client disconnected less than the reconnect window before `now`, forgets that and
+    /// returns when it first connected.
+    pub(crate) fn take_rejoin(&self, device_id: &str, now: Instant) -> Option<Instant> {
+        let window = self.reconnect_window?;
+        let departure = self
+            .departed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(device_id)?;
+        if now.duration_since(departure.left_at) < window {
+            Some(departure.connected_at)
+        } else {
+            None
+        }
+    }
+
+    /// Locks the map of connected clients, keyed by device ID.
+    pub(crate) fn lock(&self) -> MutexGuard<'_, HashMap<String, ConnectedClient>> {
+        sync::lock(&self.map, "clients")
+    }
+
+    pub(crate) fn try_lock(
+        &self,
+    ) -> TryLockResult<MutexGuard<'_, HashMap<String, ConnectedClient>>> {
+        self.map.try_lock()
+    }
+
+    /// Copies a handle to the client's device out of the map, so its endpoints can be read
+    /// without holding the lock while Windows answers.
+    pub(crate) fn device(&self, device_id: &str) -> Option<DeviceHandle> {
+        self.lock().get(device_id).map(ConnectedClient::handle)
+    }
+
+    /// Records `endpoints` as the client's reported addresses and says how they compare with the
+    /// ones reported before, in any order, or returns `None` if the client has disconnected. Only
+    /// one caller ever gets `First` for a client, so it's only ever sent one `ClientReady`.
+    pub(crate) fn update_endpoints(
+        &self,
+        device_id: &str,
+        endpoints: &[IpAddr],
+    ) -> Option<EndpointUpdate> {
+        let mut clients = self.lock();
+        let client = clients.get_mut(device_id)?;
+        let mut before = client.endpoints.clone();
+        let mut after = endpoints.to_vec();
+        before.sort();
+        after.sort();
+        let update = if before == after {
+            EndpointUpdate::Unchanged
+        } else if before.is_empty() {
+            EndpointUpdate::First
+        } else {
+            EndpointUpdate::Changed
+        };
+        client.endpoints = endpoints.to_vec();
+        Some(update)
+    }
+
+    /// Like `device()`, for every connected client.
+    pub(crate) fn devices(&self) -> Vec<(String, DeviceHandle)> {
+        self.lock()
+            .iter()
+            .map(|(device_id, client)| (device_id.clone(), client.handle()))
+            .collect()
+    }
+}
+
+/// How a client's addresses compare with the ones last reported, from
+/// `Clients::update_endpoints()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum EndpointUpdate {
+    /// The same as before, or still none.
+    Unchanged,
+    /// The first the client has had, due a `ClientReady`.
+    First,
+    /// Different from the ones reported before, due a `ClientEndpointsChanged`.
+    Changed,
+}
+
+/// One connection between this PC and a client, as reported by Windows. A client usually has one
+/// per address family, e.g. an IPv4 pair and an IPv6 link-local pair.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EndpointPair {
+    /// This PC's end of the connection.
+    pub local: IpAddr,
+    /// The client's end of the connection.
+    pub remote: IpAddr,
+}
+
+/// What Windows reports about a peer, as sent with `ClientConnected`. Anything Windows doesn't
+/// report is left empty or `false`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceInfo {
+    pub id: String,
+    /// The peer's friendly name, or its device ID if it doesn't have one.
+    pub name: String,
+    /// The `DeviceInformationKind`, e.g. `AssociationEndpoint`.
+    pub kind: String,
+    pub is_paired: bool,
+    pub can_pair: bool,
+    /// Every property Windows gave a string or boolean value for, keyed by its canonical name,
+    /// e.g. `System.Devices.Aep.Category`. Which ones are present depends on the peer and the
+    /// Windows build.
+    pub properties: BTreeMap<String, String>,
+}
+
+#[cfg(windows)]
+impl DeviceInfo {
+    pub(crate) fn read(information: &DeviceInformation) -> Self {
+        let id = information
+            .Id()
+            .map(|id| id.to_string())
+            .unwrap_or_default();
+        let name = match information.Name() {
+            Ok(name) if !name.is_empty() => name.to_string(),
+            _ => id.clone(),
+        };
+        let pairing = information.Pairing().ok();
+        let mut properties = BTreeMap::new();
+        if let Ok(map) = information.Properties() {
+            for pair in map {
+                if let (Ok(key), Ok(value)) = (pair.Key(), pair.Value()) {
+                    if let Some(value) = platform::property_string(&value) {
+                        properties.insert(key.to_string(), value);
+                    }
+                }
+            }
+        }
+        DeviceInfo {
+            id,
+            name,
+            kind: information
+                .Kind()
+                .map(platform::device_kind)
+                .unwrap_or_default(),
+            is_paired: pairing
+                .as_ref()
+                .and_then(|pairing| pairing.IsPaired().ok())
+                .unwrap_or_default(),
+            can_pair: pairing
+                .as_ref()
+                .and_then(|pairing| pairing.CanPair().ok())
+                .unwrap_or_default(),
+            properties,
+        }
+    }
+}
+
+/// A connected client as it was when asked about, as returned by
+/// `WlanHostedNetworkHelper::wait_for_client_id()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClientInfo {
+    pub device_id: String,
+    /// What Windows reported about the peer when it asked to connect.
+    pub device: DeviceInfo,
+    /// When the client connected, or first connected if it has come back within the builder's
+    /// `reconnect_window` since.
+    pub connected_at: Instant,
+}
+
+/// A client's IPv6 address along with the zone it was reported with, which link-local addresses
+/// need in order to be reached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Ipv6Endpoint {
+    pub address: Ipv6Addr,
+    /// The numeric `%zone` suffix, i.e. the interface index. `None` if there was none, or it
+    /// was an interface name rather than a number.
+    pub scope_id: Option<u32>,
+}
+
+impl Ipv6Endpoint {
+    /// The socket address for `port` at this endpoint, scope included.
+    pub fn socket_addr(&self, port: u16) -> SocketAddrV6 {
+        SocketAddrV6::new(self.address, port, 0, self.scope_id.unwrap_or(0))
+    }
+}
+
+/// The device behind a connected client: what Windows resolved, or with the `mock` feature, a peer
+/// made up by `mock_connection_request()`. Cloning only adds a reference. Without either there are
+/// no variants, as nothing can connect.
+#[derive(Clone)]
+pub(crate) enum ClientDevice {
+    #[cfg(windows)]
+    WinRt(WiFiDirectDevice),
+    #[cfg(feature = "mock")]
+    Mock(crate::mock::MockDevice),
+}
+
+impl ClientDevice {
+    pub(crate) fn is_connected(&self) -> Result<bool> {
+        match *self {
+            #[cfg(windows)]
+            Self::WinRt(ref device) => {
+                Ok(device.ConnectionStatus()? != WiFiDirectConnectionStatus::Disconnected)
+            }
+            #[cfg(feature = "mock")]
+            Self::Mock(_) => Ok(true),
+        }
+    }
+
+    fn close(&self) -> Result<()> {
+        match *self {
+            #[cfg(windows)]
+            Self::WinRt(ref device) => Ok(device.Close()?),
+            #[cfg(feature = "mock")]
+            Self::Mock(_) => Ok(()),
+        }
+    }
+}
+
+/// A peer that has connected to the hosted network.
+pub(crate) struct ConnectedClient {
+    pub(crate) device_id: String,
+    pub(crate) device: ClientDevice,
+    // what it was reported as in ClientConnected
+    pub(crate) info: DeviceInfo,
+    // ConnectionStatusChanged registration, removed when the client disconnects. Unused by mock
+    // devices, which have no handler.
+    #[cfg(windows)]
+    pub(crate) token: EventRegistrationToken,
+    pub(crate) connected_at: Instant,
+    // the addresses last reported, in ClientReady or ClientEndpointsChanged
+    pub(crate) endpoints: Vec<IpAddr>,
+}
+
+impl ConnectedClient {
+    /// Stops listening for this client's connection status changes.
+    pub(crate) fn deregister(&self) -> Result<()> {
+        match self.device {
+            #[cfg(windows)]
+            ClientDevice::WinRt(ref device) => {
+                Ok(device.RemoveConnectionStatusChanged(self.token)?)
+            }
+            #[cfg(feature = "mock")]
+            ClientDevice::Mock(_) => Ok(()),
+        }
+    }
+
+    /// Stops listening for status changes and tears down the connection to the client.
+    pub(crate) fn close(&self) -> Result<()> {
+        self.deregister()?;
+        self.device.close()
+    }
+
+    pub(crate) fn snapshot(&self) -> ClientInfo {
+        ClientInfo {
+            device_id: self.device_id.clone(),
+            device: self.info.clone(),
+            connected_at: self.connected_at,
+        }
+    }
+
+    /// A second reference to the client's device, for reading its endpoints.
+    pub(crate) fn handle(&self) -> DeviceHandle {
+        DeviceHandle {
+            device: self.device.clone(),
+        }
+    }
+}
+
+/// A connected client's device, held apart from the registry. Cloning only adds a
+/// reference, and the calls below can run on any thread, so several can be in flight at once.
+/// Once the client disconnects they fail or come back empty.
+#[derive(Clone)]
+pub(crate) struct DeviceHandle {
+    device: ClientDevice,
+}
+
+/// Which end of an endpoint pair to read.
+#[derive(Clone, Copy)]
+enum End {
+    Local,
+    Remote,
+}
+
+impl DeviceHandle {
+    /// Returns the client's address from each of its connection endpoint pairs. Host names that
+    /// aren't IP addresses are skipped.
+    pub(crate) fn remote_addresses(&self) -> Result<Vec<IpAddr>> {
+        self.addresses(End::Remote)
+    }
+
+    /// Returns the client's IPv6 addresses with their zones.
+    pub(crate) fn remote_ipv6_endpoints(&self) -> Result<Vec<Ipv6Endpoint>> {
+        Ok(self
+            .host_names(End::Remote)?
+            .iter()
+            .filter_map(|name| parse_ipv6_endpoint(name))
+            .collect())
+    }
+
+    /// Returns a socket address for `port` at each of the client's addresses, with the zone kept
+    /// on IPv6 ones.
+    pub(crate) fn remote_socket_addrs(&self, port: u16) -> Result<Vec<SocketAddr>> {
+        Ok(self
+            .host_names(End::Remote)?
+            .iter()
+            .filter_map(|name| match parse_ipv6_endpoint(name) {
+                Some(endpoint) => Some(SocketAddr::V6(endpoint.socket_addr(port))),
+                None => parse_host_name(name).map(|address| SocketAddr::new(address, port)),
+            })
+            .collect())
+    }
+
+    /// Returns this PC's address on the link to the client, from each endpoint pair.
+    pub(crate) fn local_addresses(&self) -> Result<Vec<IpAddr>> {
+        self.addresses(End::Local)
+    }
+
+    fn addresses(&self, end: End) -> Result<Vec<IpAddr>> {
+        Ok(self
+            .host_names(end)?
+            .iter()
+            .filter_map(|name| parse_host_name(name))
+            .collect())
+    }
+
+    fn host_names(&self, end: End) -> Result<Vec<String>> {
+        #[cfg(not(any(windows, feature = "mock")))]
+        let _ = end;
+        match self.device {
+            #[cfg(windows)]
+            ClientDevice::WinRt(ref device) => {
+                let mut names = Vec::new();
+                for pair in device.GetConnectionEndpointPairs()? {
+                    let host_name = match end {
+                        End::Local => pair.LocalHostName()?,
+                        End::Remote => pair.RemoteHostName()?,
+                    };
+                    names.push(host_name.DisplayName()?.to_string());
+                }
+                Ok(names)
+            }
+            #[cfg(feature = "mock")]
+            ClientDevice::Mock(ref device) => {
+                let pairs = device.endpoint_pairs().into_iter();
+                Ok(match end {
+                    End::Local => pairs.map(|pair| pair.local.to_string()).collect(),
+                    End::Remote => pairs.map(|pair| pair.remote.to_string()).collect(),
+                })
+            }
+        }
+    }
+
+    /// Returns every endpoint pair whose local and remote host names are both IP addresses.
+    pub(crate) fn endpoint_pairs(&self) -> Result<Vec<EndpointPair>> {
+        match self.device {
+            #[cfg(windows)]
+            ClientDevice::WinRt(ref device) => {
+                let mut pairs = Vec::new();
+                for pair in device.GetConnectionEndpointPairs()? {
+                    let local = parse_host_name(&pair.LocalHostName()?.DisplayName()?.to_string());
+                    let remote =
+                        parse_host_name(&pair.RemoteHostName()?.DisplayName()?.to_string());
+                    if let (Some(local), Some(remote)) = (local, remote) {
+                        pairs.push(EndpointPair { local, remote });
+                    }
+                }
+                Ok(pairs)
+            }
+            #[cfg(feature = "mock")]
+            ClientDevice::Mock(ref device) => Ok(device.endpoint_pairs()),
+        }
+    }
+
+    /// Returns the ID of the network adapter this PC uses to talk to the client, if Windows
+    /// reports one for any of its endpoint pairs.
+    pub(crate) fn adapter_id(&self) -> Result<Option<Guid>> {
+        match self.device {
+            #[cfg(windows)]
+            ClientDevice::WinRt(ref device) => {
+                for pair in device.GetConnectionEndpointPairs()? {
+                    // host names that aren't IP addresses have no adapter information
+                    if let Ok(information) = pair.LocalHostName()?.IPInformation() {
+                        let adapter = information.NetworkAdapter()?;
+                        return Ok(Some(adapter.NetworkAdapterId()?.into()));
+                    }
+                }
+                Ok(None)
+            }
+            // a made-up peer isn't on any adapter
+            #[cfg(feature = "mock")]
+            ClientDevice::Mock(_) => Ok(None),
+        }
+    }
+}
+
+/// Parses an IPv4 or IPv6 `HostName` string, dropping any brackets and IPv6 zone (`%3`) suffix.
+pub(crate) fn parse_host_name(name: &str) -> Option<IpAddr> {
+    let name = name.trim_start_matches('[').trim_end_matches(']');
+    let address = name.split('%').next().unwrap_or(name);
+    address.parse().ok()
+}
+
+/// Parses an IPv6 `HostName` string, keeping a numeric zone as the scope ID.
+pub(crate) fn parse_ipv6_endpoint(name: &str) -> Option<Ipv6Endpoint> {
+    let name = name.trim_start_matches('[').trim_end_matches(']');
+    let (address, zone) = match name.split_once('%') {
+        Some((address, zone)) => (address, Some(zone)),
+        None => (name, None),
+    };
+    Some(Ipv6Endpoint {
+        address: address.parse().ok()?,
+        scope_id: zone.and_then(|zone| zone.parse().ok()),
+    })
+}
+
+/// Pulls the peer's MAC address out of a Wi-Fi Direct device ID, which Windows builds as
+/// `WiFiDirect#xx:xx:xx:xx:xx:xx`, possibly behind an interface prefix.
+pub(crate) fn parse_mac(device_id: &str) -> Option<[u8; 6]> {
+    device_id.split('#').rev().find_map(|part| {
+        let mut mac = [0; 6];
+        let mut octets = part.split([':', '-']);
+        for byte in mac.iter_mut() {
+            let octet = octets.next()?;
+            if octet.len() != 2 {
+                return None;
+            }
+            *byte = u8::from_str_radix(octet, 16).ok()?;
+        }
+        match octets.next() {
+            Some(_) => None,
+            None => Some(mac),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_host_name, parse_ipv6_endpoint, parse_mac, Clients, Ipv6Endpoint};
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn quick_reconnects_keep_the_first_connect_time() {
+        let clients = Clients::new(Some(Duration::from_secs(5)));
+        let connected_at = Instant::now();
+        let left_at = connected_at + Duration::from_secs(60);
+        clients.record_departure("WiFiDirect#02:8b:21:42:ac:36", connected_at, left_at);
+        assert_eq!(
+            clients.take_rejoin(
+                "WiFiDirect#02:8b:21:42:ac:36",
+                left_at + Duration::from_secs(1)
+            ),
+            Some(connected_at)
+        );
+        // only once
+        assert_eq!(
+            clients.take_rejoin(
+                "WiFiDirect#02:8b:21:42:ac:36",
+                left_at + Duration::from_secs(2)
+            ),
+            None
+        );
+        clients.record_departure("WiFiDirect#02:8b:21:42:ac:36", connected_at, left_at);
+        assert_eq!(
+            clients.take_rejoin(
+                "WiFiDirect#02:8b:21:42:ac:36",
+                left_at + Duration::from_secs(5)
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn reconnects_are_new_connections_without_a_window() {
+        let clients = Clients::new(None);
+        let now = Instant::now();
+        clients.record_departure("WiFiDirect#02:8b:21:42:ac:36", now, now);
+        assert_eq!(
+            clients.take_rejoin("WiFiDirect#02:8b:21:42:ac:36", now),
+            None
+        );
+    }
+
+    #[test]
+    fn parses_host_names() {
+        assert_eq!(
+            parse_host_name("192.168.137.42"),
+            Some(IpAddr::V4(Ipv4Addr::new(192, 168, 137, 42)))
+        );
+        let link_local = Some(IpAddr::V6(Ipv6Addr::new(0xfe80, 0, 0, 0, 0x1c2, 0, 0, 1)));
+        assert_eq!(parse_host_name("fe80::1c2:0:0:1"), link_local);
+        assert_eq!(parse_host_name("fe80::1c2:0:0:1%12"), link_local);
+        assert_eq!(parse_host_name("[fe80::1c2:0:0:1%12]"), link_local);
+        assert_eq!(parse_host_name("DESKTOP-1234.local"), None);
+        assert_eq!(parse_host_name(""), None);
+    }
+
+    #[test]
+    fn ipv6_endpoints_keep_their_zone() {
+        let address = Ipv6Addr::new(0xfe80, 0, 0, 0, 0x1c2, 0, 0, 1);
+        let scoped = Ipv6Endpoint {
+            address,
+            scope_id: Some(12),
+        };
+        assert_eq!(parse_ipv6_endpoint("fe80::1c2:0:0:1%12"), Some(scoped));
+        assert_eq!(parse_ipv6_endpoint("[fe80::1c2:0:0:1%12]"), Some(scoped));
+        assert_eq!(
+            parse_ipv6_endpoint("fe80::1c2:0:0:1"),
+            Some(Ipv6Endpoint {
+                address,
+                scope_id: None
+            })
+        );
+        assert_eq!(
+            parse_ipv6_endpoint("fe80::1c2:0:0:1%wlan0").and_then(|e| e.scope_id),
+            None
+        );
+        assert_eq!(
+            scoped.socket_addr(8080).to_string(),
+            "[fe80::1c2:0:0:1%12]:8080"
+        );
+        assert_eq!(parse_ipv6_endpoint("192.168.137.42"), None);
+    }
+
+    #[test]
+    fn parses_macs_from_device_ids() {
+        let mac = Some([0x02, 0x8b, 0x21, 0x42, 0xac, 0x36]);
+        assert_eq!(parse_mac("WiFiDirect#02:8b:21:42:ac:36"), mac);
+        assert_eq!(parse_mac("\\\\?\\SWD#WiFiDirect#02-8B-21-42-AC-36"), mac);
+        assert_eq!(parse_mac("WiFiDirect#02:8b:21:42:ac"), None);
+        assert_eq!(parse_mac("WiFiDirect#02:8b:21:42:ac:36:00"), None);
+        assert_eq!(parse_mac("WiFiDirect#zz:8b:21:42:ac:36"), None);
+        assert_eq!(parse_mac(""), None);
+    }
+}
@@ -0,0 +1,182 @@
+use std::time::Duration;
+
+use crate::{AccessMode, Discoverability, OverflowPolicy, RetryPolicy, WlanHostedNetworkBuilder};
+
+/// A hosted network's settings as plain data, for keeping in a configuration file. With the
+/// `serde` feature it (de)serializes, and options left out take their default values. Turn it into
+/// a running network with `WlanHostedNetworkHelper::with_config()`, or into a builder with
+/// `WlanHostedNetworkBuilder::from()` to add callbacks that can't be serialized.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct HostedNetworkConfig {
+    /// Left empty, Windows picks one. See `WlanHostedNetworkBuilder::ssid()`.
+    pub ssid: String,
+    pub passphrase: String,
+    /// See `WlanHostedNetworkBuilder::username()`.
+    pub username: Option<String>,
+    /// See `WlanHostedNetworkBuilder::autonomous_group_owner()`.
+    pub autonomous_group_owner: bool,
+    /// See `WlanHostedNetworkBuilder::discoverability()`.
+    pub discoverability: Option<Discoverability>,
+    /// See `WlanHostedNetworkBuilder::group_owner_intent()`.
+    pub group_owner_intent: Option<u8>,
+    /// See `WlanHostedNetworkBuilder::auto_restart()`.
+    pub auto_restart: Option<RetryPolicy>,
+    /// See `WlanHostedNetworkBuilder::idle_timeout()`.
+    pub idle_timeout: Option<Duration>,
+    /// See `WlanHostedNetworkBuilder::access_mode()`.
+    pub access_mode: AccessMode,
+    /// See `WlanHostedNetworkBuilder::track_connections()`.
+    pub track_connections: bool,
+    /// See `WlanHostedNetworkBuilder::reconnect_window()`.
+    pub reconnect_window: Option<Duration>,
+    /// See `WlanHostedNetworkBuilder::max_clients()`.
+    pub max_clients: Option<usize>,
+    /// See `WlanHostedNetworkBuilder::event_buffer()`.
+    pub event_buffer: Option<(usize, OverflowPolicy)>,
+    /// See `WlanHostedNetworkBuilder::connection_history()`.
+    pub connection_history: Option<usize>,
+    /// See `WlanHostedNetworkBuilder::warn_on_ssid_collision()`.
+    pub warn_on_ssid_collision: bool,
+    /// See `WlanHostedNetworkBuilder::reject_ssid_collision()`.
+    pub reject_ssid_collision: bool,
+    /// See `WlanHostedNetworkBuilder::warn_without_direct_prefix()`.
+    pub warn_without_direct_prefix: bool,
+    /// See `WlanHostedNetworkBuilder::thread_name_prefix()`. Left out, the default is used.
+    pub thread_name_prefix: Option<String>,
+}
+
+impl HostedNetworkConfig {
+    /// A configuration with the given SSID and passphrase and every option at its default.
+    pub fn new(ssid: &str, passphrase: &str) -> Self {
+        HostedNetworkConfig {
+            ssid: ssid.to_string(),
+            passphrase: passphrase.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for HostedNetworkConfig {
+    /// The builder's defaults, with an empty SSID and passphrase that have to be filled in.
+    fn default() -> Self {
+        HostedNetworkConfig {
+            ssid: String::new(),
+            passphrase: String::new(),
+            username: None,
+            autonomous_group_owner: true,
+            discoverability: None,
+            group_owner_intent: None,
+            auto_restart: None,
+            idle_timeout: None,
+            access_mode: AccessMode::DenyList,
+            track_connections: true,
+            reconnect_window: None,
+            max_clients: None,
+            event_buffer: None,
+            connection_history: None,
+            warn_on_ssid_collision: false,
+            reject_ssid_collision: false,
+            warn_without_direct_prefix: false,
+            thread_name_prefix: None,
+        }
+    }
+}
+
+impl From<HostedNetworkConfig> for WlanHostedNetworkBuilder {
+    fn from(config: HostedNetworkConfig) -> Self {
+        let mut builder = WlanHostedNetworkBuilder::new();
+        // an empty SSID leaves it to Windows
+        if !config.ssid.is_empty() {
+            builder.ssid(&config.ssid);
+        }
+        builder
+            .passphrase(&config.passphrase)
+            .autonomous_group_owner(config.autonomous_group_owner)
+            .access_mode(config.access_mode)
+            .track_connections(config.track_connections)
+            .warn_on_ssid_collision(config.warn_on_ssid_collision)
+            .reject_ssid_collision(config.reject_ssid_collision)
+            .warn_without_direct_prefix(config.warn_without_direct_prefix);
+        if let Some(username) = &config.username {
+            builder.username(username);
+        }
+        if let Some(discoverability) = config.discoverability {
+            builder.discoverability(discoverability);
+        }
+        if let Some(intent) = config.group_owner_intent {
+            builder.group_owner_intent(intent);
+        }
+        if let Some(policy) = config.auto_restart {
+            builder.auto_restart(policy);
+        }
+        if let Some(timeout) = config.idle_timeout {
+            builder.idle_timeout(timeout);
+        }
+        if let Some(window) = config.reconnect_window {
+            builder.reconnect_window(window);
+        }
+        if let Some(limit) = config.max_clients {
+            builder.max_clients(limit);
+        }
+        if let Some((capacity, policy)) = config.event_buffer {
+            builder.event_buffer(capacity, policy);
+        }
+        if let Some(limit) = config.connection_history {
+            builder.connection_history(limit);
+        }
+        if let Some(prefix) = &config.thread_name_prefix {
+            builder.thread_name_prefix(prefix);
+        }
+        builder
+    }
+}
+
+#[cfg(all(test, any(feature = "serde", feature = "mock")))]
+mod tests {
+    use super::HostedNetworkConfig;
+    use crate::{Discoverability, RetryPolicy};
+    use std::time::Duration;
+
+    fn config() -> HostedNetworkConfig {
+        HostedNetworkConfig {
+            discoverability: Some(Discoverability::Intensive),
+            auto_restart: Some(RetryPolicy::new(3, Duration::from_secs(2))),
+            idle_timeout: Some(Duration::from_secs(300)),
+            connection_history: Some(50),
+            ..HostedNetworkConfig::new("WiFiDirectMockNetwork", "TestingThisLibrary")
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn configs_round_trip_through_json() {
+        let config = config();
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(
+            serde_json::from_str::<HostedNetworkConfig>(&json).unwrap(),
+            config
+        );
+        // anything left out takes the builder's default
+        let minimal: HostedNetworkConfig = serde_json::from_str(
+            r#"{"ssid":"WiFiDirectMockNetwork","passphrase":"TestingThisLibrary"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            minimal,
+            HostedNetworkConfig::new("WiFiDirectMockNetwork", "TestingThisLibrary")
+        );
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn helpers_start_from_a_config() {
+        let (message_tx, _message_rx) = std::sync::mpsc::channel();
+        let helper = crate::WlanHostedNetworkHelper::with_config(config(), message_tx).unwrap();
+        assert!(helper.is_running());
+        assert_eq!(helper.ssid(), "WiFiDirectMockNetwork");
+        let settings = helper.mock_publisher().settings();
+        assert_eq!(settings.discoverability, Some(Discoverability::Intensive));
+    }
+}
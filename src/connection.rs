@@ -0,0 +1,282 @@
+//! What happens to a peer between asking to connect and going away, shared by the Windows
+//! listener and the `mock` feature's fake peers: deciding whether to let it in, recording it as a
+//! client, waiting for its first address and reporting it gone.
+
+// only reached through the listener, which needs Windows, or the mock's fake peers
+#![cfg_attr(not(any(windows, feature = "mock")), allow(dead_code))]
+
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::client::{ClientDevice, ClientRegistry, ConnectedClient, EndpointUpdate};
+use crate::{
+    report_error, request, sync, ConnectionDecision, ConnectionRequestInfo, DeviceInfo, EventSink,
+    HostedNetworkError, HostedNetworkEvent, Plumbing, Result, WpsMethod,
+    CONNECTION_DECISION_TIMEOUT,
+};
+
+/// Sends `ConnectionRequested` for a peer and decides whether to let it in, going by
+/// `pause_advertising()`, `max_clients`, the access list, the `connection_filter` and the
+/// `on_connection_request` callback, in that order. A peer that isn't is turned away with
+/// `decline` and reported with `ConnectionRejected`. Returns whether the peer should go on to be
+/// resolved and registered, which without `track_connections` it shouldn't.
+pub(crate) fn admit(
+    device_id: &str,
+    device: &DeviceInfo,
+    decline: impl FnOnce() -> Result<()>,
+    plumbing: &Plumbing,
+) -> Result<bool> {
+    plumbing
+        .events
+        .emit(HostedNetworkEvent::ConnectionRequested {
+            device_id: device_id.to_string(),
+            name: device.name.clone(),
+            // the request doesn't say, see WpsMethod
+            wps_method: WpsMethod::None,
+        });
+    let mut accepted = !plumbing.paused.load(Ordering::SeqCst)
+        && !plumbing.is_full()
+        && plumbing
+            .access_list
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .permits(device_id);
+    if let (true, Some(filter)) = (accepted, &plumbing.connection_filter) {
+        accepted = filter(device_id);
+    }
+    if let (true, Some(callback)) = (accepted, &plumbing.on_connection_request) {
+        let info = ConnectionRequestInfo {
+            device_id: device_id.to_string(),
+            name: device.name.clone(),
+            is_paired: device.is_paired,
+            can_pair: device.can_pair,
+        };
+        accepted = match request::decide(
+            callback,
+            info,
+            CONNECTION_DECISION_TIMEOUT,
+            plumbing.thread_name("request"),
+        ) {
+            Ok(decision) => decision == ConnectionDecision::Accept,
+            // the callback can't be run without its thread, and nothing else can vouch for the peer
+            Err(error) => {
+                report_error(
+                    &*plumbing.events,
+                    HostedNetworkError::thread_spawn_failed(error),
+                );
+                false
+            }
+        };
+    }
+    if !accepted {
+        debug!("Connection from {} rejected", device_id);
+        decline()?;
+        plumbing
+            .events
+            .emit(HostedNetworkEvent::ConnectionRejected {
+                device_id: device_id.to_string(),
+            });
+        return Ok(false);
+    }
+    Ok(plumbing.track_connections)
+}
+
+/// Records a peer whose device has been resolved as a connected client, tells the caller about
+/// it, and starts watching for its first address. `remote_host` is the display name of its first
+/// endpoint, if it has one yet. A peer that has already gone is dropped quietly.
+pub(crate) fn register_client(
+    device_id: String,
+    device: &DeviceInfo,
+    client_device: ClientDevice,
+    remote_host: Option<String>,
+    plumbing: &Plumbing,
+) -> Result<()> {
+    let events = &plumbing.events;
+    let clients = &plumbing.clients;
+    // Like https://github.com/microsoft/Windows-classic-samples/blob/main/Samples/WiFiDirectLegacyAP/cpp/WlanHostedNetworkWinRT.cpp,
+    // keep the device and its token so the handler can be removed when the client disconnects.
+    // Hold the lock while registering so a quick disconnect can't run before the client is
+    // recorded.
+    let rejoined = clients.take_rejoin(&device_id, Instant::now());
+    let mut connected = clients.lock();
+    #[cfg(windows)]
+    let token = crate::listener::watch_connection(&client_device, plumbing)?;
+    let client = ConnectedClient {
+        device_id: device_id.clone(),
+        device: client_device,
+        info: device.clone(),
+        #[cfg(windows)]
+        token,
+        connected_at: rejoined.unwrap_or_else(Instant::now),
+        endpoints: Vec::new(),
+    };
+    if !client.device.is_connected()? {
+        // gone before the handler was registered, so it will never fire
+        client.deregister()?;
+        return Ok(());
+    }
+    connected.insert(device_id.clone(), client);
+    let count = connected.len();
+    drop(connected);
+    clients.changed.notify_all();
+    if rejoined.is_some() {
+        plumbing.trace_client(&device_id, "client reconnected");
+        events.emit(HostedNetworkEvent::ClientReconnected {
+            device_id: device_id.clone(),
+            display_name: remote_host.unwrap_or_default(),
+        });
+    } else {
+        plumbing.trace_client(&device_id, "client connected");
+        events.emit(client_connected(
+            device_id.clone(),
+            device.clone(),
+            remote_host,
+        ));
+    }
+    if plumbing.max_clients == Some(count) {
+        events.emit(HostedNetworkEvent::MaxClientsReached { max_clients: count });
+    }
+    spawn_ready_check(
+        device_id,
+        events.clone(),
+        clients.clone(),
+        plumbing.thread_name("ready"),
+    );
+    Ok(())
+}
+
+/// Makes the `ClientConnected` event for a client whose first endpoint has `remote_host` as its
+/// display name, or has no endpoint yet.
+fn client_connected(
+    device_id: String,
+    device: DeviceInfo,
+    remote_host: Option<String>,
+) -> HostedNetworkEvent {
+    HostedNetworkEvent::ClientConnected {
+        device_id,
+        endpoints_pending: remote_host.is_none(),
+        display_name: remote_host.unwrap_or_default(),
+        device,
+    }
+}
+
+/// How long a new client gets to come up with an IP address before `ClientReady` is given up on.
+#[cfg(not(test))]
+const READY_WINDOW: Duration = Duration::from_secs(10);
+// short enough for a test to outwait
+#[cfg(test)]
+const READY_WINDOW: Duration = Duration::from_secs(1);
+const READY_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Sends `ClientReady` once the client has a remote address that parses, checking again for up to
+/// `READY_WINDOW` while it has none. Gives up quietly if the client disconnects first. If the
+/// thread can't be started, that's reported and the client is checked once on this thread
+/// instead, leaving a later `refresh_client_endpoints()` to find an address that comes after.
+fn spawn_ready_check(
+    device_id: String,
+    events: Arc<dyn EventSink>,
+    clients: ClientRegistry,
+    thread_name: String,
+) {
+    let (worker_device_id, worker_events, worker_clients) =
+        (device_id.clone(), events.clone(), clients.clone());
+    let spawned = sync::spawn(thread_name, move || {
+        let deadline = Instant::now() + READY_WINDOW;
+        while !check_ready(&worker_device_id, &*worker_events, &worker_clients) {
+            if Instant::now() >= deadline {
+                warn!(
+                    "Client {} has no IP address after {:?}",
+                    worker_device_id, READY_WINDOW
+                );
+                return;
+            }
+            thread::sleep(READY_POLL_INTERVAL);
+        }
+    });
+    if let Err(error) = spawned {
+        report_error(&*events, HostedNetworkError::thread_spawn_failed(error));
+        check_ready(&device_id, &*events, &clients);
+    }
+}
+
+/// Sends `ClientReady` if the client has a remote address that parses. Returns whether there's
+/// no need to check again: it had one, it's gone, or its endpoints couldn't be read.
+fn check_ready(device_id: &str, events: &dyn EventSink, clients: &ClientRegistry) -> bool {
+    let endpoints = match clients.device(device_id) {
+        Some(device) => device.remote_addresses(),
+        None => return true,
+    };
+    match endpoints {
+        Ok(endpoints) if !endpoints.is_empty() => {
+            // refresh_client_endpoints() may have found them first and sent ClientReady itself
+            if clients.update_endpoints(device_id, &endpoints) == Some(EndpointUpdate::First) {
+                events.emit(HostedNetworkEvent::ClientReady {
+                    device_id: device_id.to_string(),
+                    endpoints,
+                });
+            }
+            true
+        }
+        Ok(_) => false,
+        Err(error) => {
+            report_error(events, error);
+            true
+        }
+    }
+}
+
+/// Removes a client that went away on its own and reports it. Returns whether it was still
+/// registered, as it isn't once `disconnect_client()` has dropped it.
+pub(crate) fn client_gone(device_id: String, plumbing: &Plumbing) -> Result<bool> {
+    let clients = &plumbing.clients;
+    let client = match clients.lock().remove(&device_id) {
+        Some(client) => client,
+        None => return Ok(false),
+    };
+    clients.changed.notify_all();
+    debug!("Client {} disconnected", device_id);
+    clients.record_departure(&device_id, client.connected_at, Instant::now());
+    plumbing.trace_client(&device_id, "client disconnected");
+    client.deregister()?;
+    plumbing
+        .events
+        .emit(HostedNetworkEvent::ClientDisconnected { device_id });
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::client_connected;
+    use crate::{DeviceInfo, HostedNetworkEvent};
+
+    #[test]
+    fn clients_without_endpoints_are_pending() {
+        let device_id = "WiFiDirect#02:8b:21:42:ac:36".to_string();
+        assert_eq!(
+            client_connected(device_id.clone(), DeviceInfo::default(), None),
+            HostedNetworkEvent::ClientConnected {
+                device_id: device_id.clone(),
+                display_name: String::new(),
+                endpoints_pending: true,
+                device: DeviceInfo::default(),
+            }
+        );
+        match client_connected(
+            device_id,
+            DeviceInfo::default(),
+            Some("192.168.137.42".to_string()),
+        ) {
+            HostedNetworkEvent::ClientConnected {
+                display_name,
+                endpoints_pending,
+                ..
+            } => {
+                assert_eq!(display_name, "192.168.137.42");
+                assert!(!endpoints_pending);
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+}
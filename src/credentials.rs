@@ -0,0 +1,85 @@
+use std::fmt;
+
+/// The SSID and passphrase the network was started with, as returned by
+/// `WlanHostedNetworkHelper::credentials()`. `Debug` leaves the passphrase out.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub(crate) ssid: String,
+    pub(crate) passphrase: String,
+}
+
+impl Credentials {
+    pub fn ssid(&self) -> &str {
+        &self.ssid
+    }
+
+    pub fn passphrase(&self) -> &str {
+        &self.passphrase
+    }
+
+    /// Formats the credentials as the `WIFI:` string phones understand when scanned as a QR code.
+    pub fn uri(&self) -> String {
+        format!(
+            "WIFI:T:WPA;S:{};P:{};;",
+            escape(&self.ssid),
+            escape(&self.passphrase)
+        )
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("ssid", &self.ssid)
+            .field("passphrase", &"<redacted>")
+            .finish()
+    }
+}
+
+// backslash-escapes the characters that have meaning in a WIFI: string
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | ';' | ',' | ':' | '"') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Credentials;
+
+    #[test]
+    fn uri_escapes_special_characters() {
+        let credentials = Credentials {
+            ssid: "WiFiDirectTestNetwork".to_string(),
+            passphrase: "TestingThisLibrary".to_string(),
+        };
+        assert_eq!(
+            credentials.uri(),
+            "WIFI:T:WPA;S:WiFiDirectTestNetwork;P:TestingThisLibrary;;"
+        );
+        let credentials = Credentials {
+            ssid: r"my;net,work".to_string(),
+            passphrase: r#"a:b\c"d"#.to_string(),
+        };
+        assert_eq!(
+            credentials.uri(),
+            r#"WIFI:T:WPA;S:my\;net\,work;P:a\:b\\c\"d;;"#
+        );
+    }
+
+    #[test]
+    fn debug_redacts_passphrase() {
+        let credentials = Credentials {
+            ssid: "WiFiDirectTestNetwork".to_string(),
+            passphrase: "TestingThisLibrary".to_string(),
+        };
+        let debug = format!("{:?}", credentials);
+        assert!(debug.contains("WiFiDirectTestNetwork"));
+        assert!(!debug.contains("TestingThisLibrary"));
+    }
+}
@@ -0,0 +1,342 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::io;
+use std::sync::mpsc::SendError;
+
+/// Reasons the hosted network can fail.
+///
+/// With the `serde` feature, errors serialize as `{"type":"<variant>","detail":<fields>}`.
+/// `Windows` errors serialize as their HRESULT and message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "detail"))]
+#[non_exhaustive]
+pub enum HostedNetworkError {
+    /// The Wi-Fi radio is switched off or otherwise unavailable.
+    RadioNotAvailable,
+    /// The Wi-Fi Direct resources are already in use by another application.
+    ResourceInUse,
+    /// Windows aborted without a specific error, usually because there is no WiFi Direct-capable card.
+    NoCapableAdapter,
+    /// The publisher aborted with a `WiFiDirectError` this crate doesn't know about, such as one
+    /// added in a newer Windows build. Carries the raw value. New codes may get their own variant
+    /// later, which is why this enum is non-exhaustive.
+    UnknownWiFiDirectError(i32),
+    /// The publisher reported a `WiFiDirectAdvertisementPublisherStatus` this crate doesn't know about.
+    UnknownStatus(i32),
+    /// A Windows Runtime callback was invoked without the sender or arguments it needs.
+    MissingEventArgs(Cow<'static, str>),
+    /// A call into the Windows Runtime failed.
+    Windows(WindowsError),
+    /// The receiving end of one of the caller's channels has been dropped.
+    ChannelClosed,
+    /// No client with this device ID is connected.
+    UnknownClient(String),
+    /// A required `WlanHostedNetworkBuilder` setting was not provided.
+    MissingConfiguration(Cow<'static, str>),
+    /// The SSID can't be used for a hosted network; the string says why.
+    InvalidSsid(String),
+    /// The passphrase can't be used for a WPA2 network; the string says why.
+    InvalidPassphrase(String),
+    /// A vendor-specific information element can't be advertised; the string says why.
+    InvalidInformationElement(String),
+    /// The builder's `group_owner_intent` can't be used; the string says why.
+    InvalidGroupOwnerIntent(String),
+    /// A Windows Runtime operation was canceled before it finished.
+    Canceled,
+    /// The hosted network's own address can't be determined until a client has connected.
+    LocalAddressUnavailable,
+    /// The hosted network's adapter can't be identified until a client has connected.
+    AdapterUnavailable,
+    /// The client's device ID doesn't contain a MAC address.
+    MacUnavailable(String),
+    /// Windows only runs one legacy access point at a time and another network is active.
+    OnlyOneNetworkSupported,
+    /// No network with this name is known to the `HostedNetworkManager`.
+    UnknownNetwork(String),
+    /// A wait ran out of time before what it was waiting for happened.
+    Timeout,
+    /// Other access points are already broadcasting the SSID, and the builder's
+    /// `reject_ssid_collision` is set.
+    SsidCollision { ssid: String, bssid_count: usize },
+    /// Hosted networks need Windows, and this build is for another platform without the `mock`
+    /// feature.
+    UnsupportedPlatform,
+    /// One of the crate's threads couldn't be started; the string says which and why.
+    ThreadSpawnFailed(String),
+}
+
+/// A failed Windows Runtime call, as carried by `HostedNetworkError::Windows`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WindowsError {
+    /// The HRESULT the call failed with, e.g. `0x80070032` for `ERROR_NOT_SUPPORTED`.
+    pub code: i32,
+    /// What Windows said about the failure. Can be empty.
+    pub message: String,
+}
+
+impl fmt::Display for WindowsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.message.is_empty() {
+            write!(f, "{:#010x}", self.code)
+        } else {
+            write!(f, "{} ({:#010x})", self.message, self.code)
+        }
+    }
+}
+
+impl std::error::Error for WindowsError {}
+
+/// Broad categories of `HostedNetworkError`, for branching on what went wrong without matching
+/// every variant or the HRESULT of a `Windows` error. See `HostedNetworkError::kind()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The PC or its Wi-Fi adapter can't host a Wi-Fi Direct network.
+    NotSupported,
+    /// The Wi-Fi radio is off, e.g. in airplane mode.
+    RadioOff,
+    /// Windows refused access, e.g. because the app lacks the `wiFiControl` capability.
+    AccessDenied,
+    /// Another application or network is using the Wi-Fi Direct resources.
+    ResourceInUse,
+    /// A setting given to the builder or helper was rejected.
+    InvalidConfiguration,
+    /// A wait ran out of time.
+    TimedOut,
+    /// The operation was canceled.
+    Canceled,
+    /// Anything else.
+    Other,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotSupported => write!(f, "not supported"),
+            Self::RadioOff => write!(f, "radio off"),
+            Self::AccessDenied => write!(f, "access denied"),
+            Self::ResourceInUse => write!(f, "resource in use"),
+            Self::InvalidConfiguration => write!(f, "invalid configuration"),
+            Self::TimedOut => write!(f, "timed out"),
+            Self::Canceled => write!(f, "canceled"),
+            Self::Other => write!(f, "other"),
+        }
+    }
+}
+
+// HRESULTs the Wi-Fi Direct APIs are known to fail with, as HRESULT_FROM_WIN32 or COM codes
+const E_NOTIMPL: u32 = 0x8000_4001;
+const E_ABORT: u32 = 0x8000_4004;
+const E_ACCESSDENIED: u32 = 0x8007_0005;
+const ERROR_NOT_READY: u32 = 0x8007_0015;
+const ERROR_NOT_SUPPORTED: u32 = 0x8007_0032;
+const E_INVALIDARG: u32 = 0x8007_0057;
+const ERROR_BUSY: u32 = 0x8007_00AA;
+const ERROR_CANCELLED: u32 = 0x8007_04C7;
+const ERROR_TIMEOUT: u32 = 0x8007_05B4;
+const ERROR_DEVICE_NOT_AVAILABLE: u32 = 0x8007_10DF;
+
+/// What a failing HRESULT means, as far as this crate can tell.
+fn hresult_kind(code: i32) -> ErrorKind {
+    match code as u32 {
+        E_NOTIMPL | ERROR_NOT_SUPPORTED => ErrorKind::NotSupported,
+        ERROR_NOT_READY | ERROR_DEVICE_NOT_AVAILABLE => ErrorKind::RadioOff,
+        E_ACCESSDENIED => ErrorKind::AccessDenied,
+        ERROR_BUSY => ErrorKind::ResourceInUse,
+        E_INVALIDARG => ErrorKind::InvalidConfiguration,
+        ERROR_TIMEOUT => ErrorKind::TimedOut,
+        E_ABORT | ERROR_CANCELLED => ErrorKind::Canceled,
+        _ => ErrorKind::Other,
+    }
+}
+
+impl HostedNetworkError {
+    #[cfg(windows)]
+    pub(crate) fn missing_event_args(callback: &'static str) -> Self {
+        Self::MissingEventArgs(Cow::Borrowed(callback))
+    }
+
+    pub(crate) fn missing_configuration(setting: &'static str) -> Self {
+        Self::MissingConfiguration(Cow::Borrowed(setting))
+    }
+
+    /// From the error `sync::spawn()` failed with.
+    pub(crate) fn thread_spawn_failed(error: io::Error) -> Self {
+        Self::ThreadSpawnFailed(error.to_string())
+    }
+
+    /// The HRESULT behind a `Windows` error, for callers that need to tell specific failures
+    /// apart.
+    pub fn hresult(&self) -> Option<i32> {
+        match self {
+            Self::Windows(error) => Some(error.code),
+            _ => None,
+        }
+    }
+
+    /// Which broad category the error falls into. `Windows` errors are sorted by their HRESULT,
+    /// so e.g. `ERROR_NOT_SUPPORTED` from any call comes out as `ErrorKind::NotSupported`.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::RadioNotAvailable => ErrorKind::RadioOff,
+            Self::ResourceInUse | Self::OnlyOneNetworkSupported => ErrorKind::ResourceInUse,
+            Self::NoCapableAdapter | Self::UnsupportedPlatform => ErrorKind::NotSupported,
+            Self::Windows(error) => hresult_kind(error.code),
+            Self::MissingConfiguration(_)
+            | Self::InvalidSsid(_)
+            | Self::InvalidPassphrase(_)
+            | Self::InvalidInformationElement(_)
+            | Self::InvalidGroupOwnerIntent(_)
+            | Self::SsidCollision { .. } => ErrorKind::InvalidConfiguration,
+            Self::Timeout => ErrorKind::TimedOut,
+            Self::Canceled => ErrorKind::Canceled,
+            _ => ErrorKind::Other,
+        }
+    }
+
+    /// Whether the condition is likely to clear up by itself, so starting again is worth a try.
+    pub(crate) fn is_transient(&self) -> bool {
+        matches!(self, Self::RadioNotAvailable | Self::ResourceInUse)
+    }
+}
+
+impl fmt::Display for HostedNetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RadioNotAvailable => write!(f, "Radio not available"),
+            Self::ResourceInUse => write!(f, "Resource in use"),
+            Self::NoCapableAdapter => write!(f, "No WiFi Direct-capable card or other error"),
+            Self::UnknownWiFiDirectError(code) => write!(f, "Unknown WiFiDirectError {}", code),
+            Self::UnknownStatus(status) => write!(f, "Unknown publisher status {}", status),
+            Self::MissingEventArgs(callback) => write!(f, "No arguments in {}", callback),
+            Self::Windows(error) => write!(
+                f,
+                "Windows Runtime error {:#010x} ({}): {}",
+                error.code,
+                hresult_kind(error.code),
+                error.message
+            ),
+            Self::ChannelClosed => write!(f, "Receiving end of channel was dropped"),
+            Self::UnknownClient(device_id) => write!(f, "No connected client {}", device_id),
+            Self::MissingConfiguration(setting) => write!(f, "No {} was provided", setting),
+            Self::InvalidSsid(reason) => write!(f, "Invalid SSID: {}", reason),
+            Self::InvalidPassphrase(reason) => write!(f, "Invalid passphrase: {}", reason),
+            Self::InvalidInformationElement(reason) => {
+                write!(f, "Invalid information element: {}", reason)
+            }
+            Self::InvalidGroupOwnerIntent(reason) => {
+                write!(f, "Invalid group owner intent: {}", reason)
+            }
+            Self::Canceled => write!(f, "Operation was canceled"),
+            Self::Timeout => write!(f, "Timed out"),
+            Self::SsidCollision { ssid, bssid_count } => write!(
+                f,
+                "SSID {} is already broadcast by {} access point(s)",
+                ssid, bssid_count
+            ),
+            Self::OnlyOneNetworkSupported => {
+                write!(f, "Only one hosted network can run at a time")
+            }
+            Self::UnknownNetwork(name) => write!(f, "No network named {}", name),
+            Self::MacUnavailable(device_id) => write!(f, "No MAC address in {}", device_id),
+            Self::AdapterUnavailable => {
+                write!(
+                    f,
+                    "Hosted network adapter is unknown until a client connects"
+                )
+            }
+            Self::LocalAddressUnavailable => {
+                write!(f, "Local address is unknown until a client connects")
+            }
+            Self::UnsupportedPlatform => write!(f, "Hosted networks need Windows"),
+            Self::ThreadSpawnFailed(reason) => write!(f, "Couldn't start thread {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for HostedNetworkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Windows(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl<T> From<SendError<T>> for HostedNetworkError {
+    fn from(_: SendError<T>) -> Self {
+        Self::ChannelClosed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ErrorKind, HostedNetworkError, WindowsError};
+    use crate::{report_error, HostedNetworkEvent};
+    use std::io;
+    use std::sync::mpsc;
+
+    fn windows(code: u32) -> HostedNetworkError {
+        HostedNetworkError::Windows(WindowsError {
+            code: code as i32,
+            message: String::new(),
+        })
+    }
+
+    #[test]
+    fn unknown_wifi_direct_error_is_reported() {
+        let (tx, rx) = mpsc::channel();
+        let error = HostedNetworkError::UnknownWiFiDirectError(42);
+        report_error(&tx, error.clone());
+        assert_eq!(rx.recv().unwrap(), HostedNetworkEvent::Error { error });
+    }
+
+    #[test]
+    fn hresult_is_only_reported_for_windows_errors() {
+        // ERROR_GEN_FAILURE
+        assert_eq!(windows(0x8007001F).hresult(), Some(0x8007001Fu32 as i32));
+        assert_eq!(HostedNetworkError::ResourceInUse.hresult(), None);
+    }
+
+    #[test]
+    fn common_hresults_have_a_kind() {
+        // ERROR_NOT_SUPPORTED
+        let error = windows(0x80070032);
+        assert_eq!(error.kind(), ErrorKind::NotSupported);
+        assert!(error
+            .to_string()
+            .starts_with("Windows Runtime error 0x80070032 (not supported)"));
+        // ERROR_DEVICE_NOT_AVAILABLE, which is what a switched off radio looks like
+        let error = windows(0x800710DF);
+        assert_eq!(error.kind(), ErrorKind::RadioOff);
+        // ERROR_GEN_FAILURE
+        let error = windows(0x8007001F);
+        assert_eq!(error.kind(), ErrorKind::Other);
+        assert_eq!(
+            HostedNetworkError::RadioNotAvailable.kind(),
+            ErrorKind::RadioOff
+        );
+    }
+
+    #[test]
+    fn thread_spawn_failures_name_the_thread() {
+        let error = HostedNetworkError::thread_spawn_failed(io::Error::other(
+            "wifidirect-ap-ready: Resource temporarily unavailable",
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Couldn't start thread wifidirect-ap-ready: Resource temporarily unavailable"
+        );
+        assert_eq!(error.kind(), ErrorKind::Other);
+    }
+
+    #[test]
+    fn report_error_without_receiver_does_not_panic() {
+        let (tx, rx) = mpsc::channel();
+        drop(rx);
+        report_error(&tx, HostedNetworkError::UnknownStatus(7));
+    }
+}
@@ -0,0 +1,320 @@
+use std::fmt;
+use std::net::IpAddr;
+use std::time::SystemTime;
+
+use crate::client::DeviceInfo;
+use crate::error::HostedNetworkError;
+use crate::request::WpsMethod;
+
+/// Status updates sent from the Windows Runtime callbacks back to the caller.
+///
+/// With the `serde` feature, events serialize as objects whose `type` field names the variant,
+/// e.g. `{"type":"Started","ssid":"MyNetwork"}`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type"))]
+pub enum HostedNetworkEvent {
+    /// The advertisement publisher has been created and is starting. Sent once each time the
+    /// network starts or restarts, always before the `Started` or `Aborted` that ends the attempt.
+    Created,
+    /// The builder's `warn_on_ssid_collision` scan found `bssid_count` access points already
+    /// broadcasting `ssid`, so clients may join one of them instead. The network starts anyway.
+    SsidCollisionWarning { ssid: String, bssid_count: usize },
+    /// The builder's `warn_without_direct_prefix` is set and `ssid` doesn't start with
+    /// `DIRECT-`, so peers that expect the Wi-Fi Direct naming may not recognize the network.
+    /// The network starts anyway.
+    SsidPrefixWarning { ssid: String },
+    /// The access point is up and accepting connections.
+    Started { ssid: String },
+    /// The access point has been stopped.
+    Stopped,
+    /// Windows tore down the access point, or it could not be started.
+    Aborted { error: HostedNetworkError },
+    /// The network aborted and is being restarted under the builder's `auto_restart` policy.
+    /// `attempt` counts from 1.
+    Retrying { attempt: u32 },
+    /// A network that was up aborted and is being restarted under the builder's `auto_restart`
+    /// policy. This is what an adapter reset looks like, as on resume from sleep. `attempt`
+    /// counts from 1, and any further attempts are sent as `Retrying`.
+    PowerResumeRestart { attempt: u32 },
+    /// The network was stopped because no clients were connected for the builder's
+    /// `idle_timeout`.
+    IdleStopped,
+    /// `stop()` was called while the publisher was not running.
+    NotRunning,
+    /// `pause_advertising()` was called, so new peers are turned away until
+    /// `resume_advertising()`.
+    AdvertisingPaused,
+    /// `resume_advertising()` was called, so new peers are accepted again.
+    AdvertisingResumed,
+    /// A peer asked to connect to the access point. `name` is the peer's friendly name, or its
+    /// device ID if it doesn't have one. `wps_method` is how the peer is configuring, though
+    /// Windows doesn't report it for legacy clients, so it is `WpsMethod::None`.
+    ConnectionRequested {
+        device_id: String,
+        name: String,
+        wps_method: WpsMethod,
+    },
+    /// A peer asked to connect and was turned away, because new peers were paused, the network
+    /// was full, or by the helper's access list, or the builder's `connection_filter` or
+    /// `on_connection_request`.
+    ConnectionRejected { device_id: String },
+    /// A peer has connected. `display_name` is the peer's remote host name, usually its IP address,
+    /// and `device` is what Windows reported about the peer when it asked to connect. Windows can
+    /// report the connection before the peer has any endpoints, in which case `display_name` is
+    /// empty, `endpoints_pending` is set, and `ClientReady` follows once an address appears.
+    ClientConnected {
+        device_id: String,
+        display_name: String,
+        endpoints_pending: bool,
+        device: DeviceInfo,
+    },
+    /// The builder's `max_clients` peers are connected, so more are turned away until one
+    /// leaves. Sent each time the count reaches the limit.
+    MaxClientsReached { max_clients: usize },
+    /// A peer connected again within the builder's `reconnect_window` of disconnecting, and is
+    /// treated as the same connection rather than a new one.
+    ClientReconnected {
+        device_id: String,
+        display_name: String,
+    },
+    /// A connected peer has an IP address, so it can be reached at `endpoints`. Sent once per
+    /// connection, after `ClientConnected`. Windows can take a moment to assign the address, so
+    /// this may lag behind. A peer that gets no address within ten seconds only sends it if
+    /// `refresh_client_endpoints()` finds one later.
+    ClientReady {
+        device_id: String,
+        endpoints: Vec<IpAddr>,
+    },
+    /// `refresh_client_endpoints()` found a connected peer at different IP addresses than were
+    /// last reported, as after it renews its DHCP lease. `endpoints` are the new ones.
+    ClientEndpointsChanged {
+        device_id: String,
+        endpoints: Vec<IpAddr>,
+    },
+    /// A peer asked to connect, but Windows couldn't resolve its `WiFiDirectDevice`.
+    ClientConnectFailed {
+        device_id: String,
+        error: HostedNetworkError,
+    },
+    /// A previously connected peer has gone away.
+    ClientDisconnected { device_id: String },
+    /// Something went wrong inside one of the Windows Runtime callbacks.
+    Error { error: HostedNetworkError },
+    /// The builder's `event_buffer` was full and `count` events were thrown away since the last
+    /// one delivered.
+    EventsDropped { count: usize },
+}
+
+/// An event together with when it was emitted, as sent to the builder's `timed_event_sender()`.
+/// The time is taken in the callback that raised the event, so it is accurate even when an
+/// `event_buffer` delays delivery.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimedEvent {
+    pub at: SystemTime,
+    pub event: HostedNetworkEvent,
+}
+
+impl fmt::Display for HostedNetworkEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Created => write!(f, "Hosted network created"),
+            Self::SsidCollisionWarning { ssid, bssid_count } => write!(
+                f,
+                "SSID {} is already broadcast by {} access point(s)",
+                ssid, bssid_count
+            ),
+            Self::SsidPrefixWarning { ssid } => {
+                write!(f, "SSID {} doesn't start with DIRECT-", ssid)
+            }
+            Self::Started { ssid } => write!(f, "Hosted network {} has started", ssid),
+            Self::Stopped => write!(f, "Hosted network stopped"),
+            Self::Aborted { error } => write!(f, "Hosted network aborted: {}", error),
+            Self::Retrying { attempt } => {
+                write!(f, "Restarting hosted network, attempt {}", attempt)
+            }
+            Self::PowerResumeRestart { attempt } => write!(
+                f,
+                "Restarting hosted network after it went down, attempt {}",
+                attempt
+            ),
+            Self::IdleStopped => write!(f, "Hosted network stopped after idling"),
+            Self::NotRunning => write!(
+                f,
+                "Stop called but WiFiDirectAdvertisementPublisher is not running"
+            ),
+            Self::AdvertisingPaused => write!(f, "Hosted network paused for new clients"),
+            Self::AdvertisingResumed => write!(f, "Hosted network accepting new clients again"),
+            Self::ConnectionRequested {
+                device_id, name, ..
+            } => {
+                write!(f, "Connection requested by {} ({})", name, device_id)
+            }
+            Self::ConnectionRejected { device_id } => {
+                write!(f, "Connection from {} rejected", device_id)
+            }
+            Self::ClientConnected {
+                device_id,
+                endpoints_pending: true,
+                ..
+            } => write!(f, "Client {} connected, address pending", device_id),
+            Self::ClientConnected {
+                device_id,
+                display_name,
+                ..
+            } => write!(f, "Client {} ({}) connected", display_name, device_id),
+            Self::MaxClientsReached { max_clients } => {
+                write!(f, "Hosted network is full with {} clients", max_clients)
+            }
+            Self::ClientReconnected {
+                device_id,
+                display_name,
+            } => write!(f, "Client {} ({}) reconnected", display_name, device_id),
+            Self::ClientReady {
+                device_id,
+                endpoints,
+            } => {
+                let endpoints: Vec<String> = endpoints.iter().map(IpAddr::to_string).collect();
+                write!(f, "Client {} ready at {}", device_id, endpoints.join(", "))
+            }
+            Self::ClientEndpointsChanged {
+                device_id,
+                endpoints,
+            } => {
+                let endpoints: Vec<String> = endpoints.iter().map(IpAddr::to_string).collect();
+                write!(f, "Client {} moved to {}", device_id, endpoints.join(", "))
+            }
+            Self::ClientConnectFailed { device_id, error } => {
+                write!(f, "Client {} failed to connect: {}", device_id, error)
+            }
+            Self::ClientDisconnected { device_id } => {
+                write!(f, "Client {} disconnected", device_id)
+            }
+            Self::Error { error } => write!(f, "Hosted network error: {}", error),
+            Self::EventsDropped { count } => write!(f, "{} events dropped", count),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::HostedNetworkEvent;
+    use crate::{DeviceInfo, HostedNetworkError, WindowsError, WpsMethod};
+
+    fn round_trip(event: &HostedNetworkEvent) -> HostedNetworkEvent {
+        serde_json::from_str(&serde_json::to_string(event).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn events_round_trip_through_json() {
+        let device_id = "Wi-Fi Direct device".to_string();
+        let started = HostedNetworkEvent::Started {
+            ssid: "WiFiDirectTestNetwork".to_string(),
+        };
+        let events = vec![
+            HostedNetworkEvent::Created,
+            HostedNetworkEvent::SsidCollisionWarning {
+                ssid: "WiFiDirectTestNetwork".to_string(),
+                bssid_count: 2,
+            },
+            HostedNetworkEvent::SsidPrefixWarning {
+                ssid: "WiFiDirectTestNetwork".to_string(),
+            },
+            started.clone(),
+            HostedNetworkEvent::Stopped,
+            HostedNetworkEvent::Aborted {
+                error: HostedNetworkError::RadioNotAvailable,
+            },
+            HostedNetworkEvent::Retrying { attempt: 2 },
+            HostedNetworkEvent::PowerResumeRestart { attempt: 1 },
+            HostedNetworkEvent::IdleStopped,
+            HostedNetworkEvent::NotRunning,
+            HostedNetworkEvent::AdvertisingPaused,
+            HostedNetworkEvent::AdvertisingResumed,
+            HostedNetworkEvent::ConnectionRequested {
+                device_id: device_id.clone(),
+                name: "Pixel 7".to_string(),
+                wps_method: WpsMethod::None,
+            },
+            HostedNetworkEvent::ConnectionRejected {
+                device_id: device_id.clone(),
+            },
+            HostedNetworkEvent::ClientConnected {
+                device_id: device_id.clone(),
+                display_name: "192.168.137.42".to_string(),
+                endpoints_pending: false,
+                device: DeviceInfo {
+                    id: device_id.clone(),
+                    name: "Pixel 7".to_string(),
+                    kind: "AssociationEndpoint".to_string(),
+                    is_paired: false,
+                    can_pair: true,
+                    properties: [(
+                        "System.Devices.Aep.Category".to_string(),
+                        "Phone".to_string(),
+                    )]
+                    .into(),
+                },
+            },
+            HostedNetworkEvent::MaxClientsReached { max_clients: 8 },
+            HostedNetworkEvent::ClientReconnected {
+                device_id: device_id.clone(),
+                display_name: "192.168.137.42".to_string(),
+            },
+            HostedNetworkEvent::ClientReady {
+                device_id: device_id.clone(),
+                endpoints: vec!["192.168.137.42".parse().unwrap()],
+            },
+            HostedNetworkEvent::ClientEndpointsChanged {
+                device_id: device_id.clone(),
+                endpoints: vec!["192.168.137.43".parse().unwrap()],
+            },
+            HostedNetworkEvent::ClientConnectFailed {
+                device_id: device_id.clone(),
+                error: HostedNetworkError::Canceled,
+            },
+            HostedNetworkEvent::ClientDisconnected { device_id },
+            HostedNetworkEvent::Error {
+                error: HostedNetworkError::UnknownStatus(7),
+            },
+            HostedNetworkEvent::Error {
+                error: HostedNetworkError::MissingEventArgs("status change callback".into()),
+            },
+            HostedNetworkEvent::Error {
+                error: HostedNetworkError::MissingConfiguration("passphrase".into()),
+            },
+            HostedNetworkEvent::EventsDropped { count: 3 },
+        ];
+        for event in &events {
+            assert_eq!(&round_trip(event), event);
+        }
+        assert_eq!(
+            serde_json::to_string(&started).unwrap(),
+            r#"{"type":"Started","ssid":"WiFiDirectTestNetwork"}"#
+        );
+    }
+
+    #[test]
+    fn windows_errors_keep_their_hresult() {
+        // ERROR_GEN_FAILURE
+        let event = HostedNetworkEvent::Error {
+            error: HostedNetworkError::Windows(WindowsError {
+                code: 0x8007001Fu32 as i32,
+                message: "A device attached to the system is not functioning.".to_string(),
+            }),
+        };
+        assert_eq!(round_trip(&event), event);
+    }
+
+    #[test]
+    fn names_serialize_as_plain_strings() {
+        let event = HostedNetworkEvent::Error {
+            error: HostedNetworkError::MissingConfiguration("ssid".into()),
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"type":"Error","error":{"type":"MissingConfiguration","detail":"ssid"}}"#
+        );
+    }
+}
@@ -0,0 +1,247 @@
+//! C-compatible bindings, enabled by the `ffi` feature. `include/wifidirect_legacy_ap.h` declares
+//! them for C and C++ consumers; it can be regenerated with
+//! `cbindgen --config cbindgen.toml --output include/wifidirect_legacy_ap.h`.
+
+use std::ffi::{c_char, c_void, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use crate::{
+    HostedNetworkEvent, OverflowPolicy, WlanHostedNetworkBuilder, WlanHostedNetworkHelper,
+};
+
+// events held for the callback thread before the oldest are dropped
+const FFI_EVENT_BUFFER: usize = 256;
+
+/// Opaque handle to a running hosted network, returned by `whn_start()`.
+pub struct WhnHelper(WlanHostedNetworkHelper);
+
+/// Receives each event as its type, e.g. `ClientConnected`, and its description. Both strings are
+/// UTF-8, passed with their length in bytes and also NUL-terminated, and are only valid for the
+/// duration of the call. Every call for a network comes from the same thread.
+pub type WhnEventCallback = extern "C" fn(
+    event_type: *const c_char,
+    event_type_len: usize,
+    payload: *const c_char,
+    payload_len: usize,
+    userdata: *mut c_void,
+);
+
+// the caller promises the userdata can be used from the callback thread
+struct Userdata(*mut c_void);
+unsafe impl Send for Userdata {}
+unsafe impl Sync for Userdata {}
+
+/// Starts a hosted network and returns a handle to it, or null if the arguments are invalid or
+/// the network couldn't be created. `callback` is called with every event until `whn_free()`.
+///
+/// # Safety
+///
+/// `ssid` and `password` must point to `ssid_len` and `password_len` readable bytes of UTF-8.
+/// `userdata` is passed to `callback` as is, from another thread, and must stay valid until
+/// `whn_free()` returns.
+#[no_mangle]
+pub unsafe extern "C" fn whn_start(
+    ssid: *const c_char,
+    ssid_len: usize,
+    password: *const c_char,
+    password_len: usize,
+    callback: Option<WhnEventCallback>,
+    userdata: *mut c_void,
+) -> *mut WhnHelper {
+    let (ssid, password, callback) = match (
+        str_arg(ssid, ssid_len),
+        str_arg(password, password_len),
+        callback,
+    ) {
+        (Some(ssid), Some(password), Some(callback)) => (ssid, password, callback),
+        _ => return ptr::null_mut(),
+    };
+    let userdata = Userdata(userdata);
+    let started = panic::catch_unwind(AssertUnwindSafe(|| {
+        WlanHostedNetworkBuilder::new()
+            .ssid(ssid)
+            .passphrase(password)
+            .event_sink(move |event: HostedNetworkEvent| {
+                call(callback, &event, &userdata);
+            })
+            // the buffer's worker is the one thread the callback is called from
+            .event_buffer(FFI_EVENT_BUFFER, OverflowPolicy::Block)
+            .build()
+    }));
+    match started {
+        Ok(Ok(helper)) => Box::into_raw(Box::new(WhnHelper(helper))),
+        Ok(Err(error)) => {
+            error!("Couldn't start hosted network: {}", error);
+            ptr::null_mut()
+        }
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Stops the network, which can be started again by freeing the handle and calling
+/// `whn_start()`. Returns 0 on success, -1 for a null handle and 1 if stopping failed.
+///
+/// # Safety
+///
+/// `handle` must be null or a handle from `whn_start()` that hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn whn_stop(handle: *mut WhnHelper) -> i32 {
+    let helper = match handle.as_ref() {
+        Some(helper) => helper,
+        None => return -1,
+    };
+    match panic::catch_unwind(AssertUnwindSafe(|| helper.0.stop())) {
+        Ok(Ok(())) => 0,
+        _ => 1,
+    }
+}
+
+/// Stops the network if it's running and frees the handle, waiting for the callback thread to
+/// finish so the callback isn't called again. See `WlanHostedNetworkHelper::shutdown()`. Null is
+/// ignored.
+///
+/// # Safety
+///
+/// `handle` must be null or a handle from `whn_start()` that hasn't been freed, and must not be
+/// used again. It mustn't be freed from inside the callback.
+#[no_mangle]
+pub unsafe extern "C" fn whn_free(handle: *mut WhnHelper) {
+    if handle.is_null() {
+        return;
+    }
+    let helper = Box::from_raw(handle);
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| helper.0.shutdown()));
+}
+
+// Reads a UTF-8 argument, rejecting null pointers and invalid text.
+unsafe fn str_arg<'a>(text: *const c_char, len: usize) -> Option<&'a str> {
+    if text.is_null() {
+        return None;
+    }
+    std::str::from_utf8(slice::from_raw_parts(text.cast::<u8>(), len)).ok()
+}
+
+fn call(callback: WhnEventCallback, event: &HostedNetworkEvent, userdata: &Userdata) {
+    let event_type = event_type(event);
+    // a description can't contain NUL unless a peer's name does, so strip them rather than fail
+    let payload = event.to_string().replace('\0', "");
+    let event_type_c = CString::new(event_type).expect("event types have no NULs");
+    let payload_c = CString::new(payload).expect("NULs were removed");
+    callback(
+        event_type_c.as_ptr(),
+        event_type.len(),
+        payload_c.as_ptr(),
+        payload_c.as_bytes().len(),
+        userdata.0,
+    );
+}
+
+fn event_type(event: &HostedNetworkEvent) -> &'static str {
+    match event {
+        HostedNetworkEvent::Created => "Created",
+        HostedNetworkEvent::SsidCollisionWarning { .. } => "SsidCollisionWarning",
+        HostedNetworkEvent::SsidPrefixWarning { .. } => "SsidPrefixWarning",
+        HostedNetworkEvent::Started { .. } => "Started",
+        HostedNetworkEvent::Stopped => "Stopped",
+        HostedNetworkEvent::Aborted { .. } => "Aborted",
+        HostedNetworkEvent::Retrying { .. } => "Retrying",
+        HostedNetworkEvent::PowerResumeRestart { .. } => "PowerResumeRestart",
+        HostedNetworkEvent::IdleStopped => "IdleStopped",
+        HostedNetworkEvent::NotRunning => "NotRunning",
+        HostedNetworkEvent::AdvertisingPaused => "AdvertisingPaused",
+        HostedNetworkEvent::AdvertisingResumed => "AdvertisingResumed",
+        HostedNetworkEvent::ConnectionRequested { .. } => "ConnectionRequested",
+        HostedNetworkEvent::ConnectionRejected { .. } => "ConnectionRejected",
+        HostedNetworkEvent::ClientConnected { .. } => "ClientConnected",
+        HostedNetworkEvent::MaxClientsReached { .. } => "MaxClientsReached",
+        HostedNetworkEvent::ClientReconnected { .. } => "ClientReconnected",
+        HostedNetworkEvent::ClientReady { .. } => "ClientReady",
+        HostedNetworkEvent::ClientEndpointsChanged { .. } => "ClientEndpointsChanged",
+        HostedNetworkEvent::ClientConnectFailed { .. } => "ClientConnectFailed",
+        HostedNetworkEvent::ClientDisconnected { .. } => "ClientDisconnected",
+        HostedNetworkEvent::Error { .. } => "Error",
+        HostedNetworkEvent::EventsDropped { .. } => "EventsDropped",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{whn_free, whn_start, whn_stop};
+    use std::ffi::{c_char, c_void, CStr};
+    use std::ptr;
+    use std::sync::Mutex;
+
+    extern "C" fn record(
+        event_type: *const c_char,
+        event_type_len: usize,
+        _payload: *const c_char,
+        _payload_len: usize,
+        userdata: *mut c_void,
+    ) {
+        let seen = unsafe { &*(userdata as *const Mutex<Vec<String>>) };
+        let event_type = unsafe { CStr::from_ptr(event_type) }.to_str().unwrap();
+        assert_eq!(event_type.len(), event_type_len);
+        seen.lock().unwrap().push(event_type.to_string());
+    }
+
+    #[test]
+    fn invalid_arguments_give_null() {
+        let password = "TestingThisLibrary";
+        let invalid = [0xffu8, 0xfe];
+        unsafe {
+            assert!(whn_start(
+                ptr::null(),
+                0,
+                password.as_ptr().cast(),
+                password.len(),
+                Some(record),
+                ptr::null_mut()
+            )
+            .is_null());
+            assert!(whn_start(
+                invalid.as_ptr().cast(),
+                invalid.len(),
+                password.as_ptr().cast(),
+                password.len(),
+                Some(record),
+                ptr::null_mut()
+            )
+            .is_null());
+            assert!(whn_start(
+                password.as_ptr().cast(),
+                password.len(),
+                password.as_ptr().cast(),
+                password.len(),
+                None,
+                ptr::null_mut()
+            )
+            .is_null());
+            assert_eq!(whn_stop(ptr::null_mut()), -1);
+            whn_free(ptr::null_mut());
+        }
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn events_reach_the_callback() {
+        let seen = Mutex::new(Vec::<String>::new());
+        let ssid = "WiFiDirectMockNetwork";
+        let password = "TestingThisLibrary";
+        unsafe {
+            let handle = whn_start(
+                ssid.as_ptr().cast(),
+                ssid.len(),
+                password.as_ptr().cast(),
+                password.len(),
+                Some(record),
+                &seen as *const Mutex<Vec<String>> as *mut c_void,
+            );
+            assert!(!handle.is_null());
+            assert_eq!(whn_stop(handle), 0);
+            whn_free(handle);
+        }
+        assert_eq!(*seen.lock().unwrap(), vec!["Created", "Started", "Stopped"]);
+    }
+}
@@ -0,0 +1,43 @@
+use std::fmt;
+
+/// A GUID as Windows uses them, such as the interface GUID of the hosted network's adapter.
+/// Formats the way Windows tools expect, e.g. `{6B29FC40-CA47-1067-B31D-00DD010662DA}`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Guid(pub u128);
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes = self.0.to_be_bytes();
+        write!(
+            f,
+            "{{{:08X}-{:04X}-{:04X}-{:04X}-{:012X}}}",
+            u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            u16::from_be_bytes([bytes[4], bytes[5]]),
+            u16::from_be_bytes([bytes[6], bytes[7]]),
+            u16::from_be_bytes([bytes[8], bytes[9]]),
+            self.0 & 0xFFFF_FFFF_FFFF
+        )
+    }
+}
+
+impl fmt::Debug for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Guid({})", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Guid;
+
+    #[test]
+    fn guid_is_braced_and_uppercase() {
+        let guid = Guid(0x6b29fc40_ca47_1067_b31d_00dd010662da);
+        assert_eq!(guid.to_string(), "{6B29FC40-CA47-1067-B31D-00DD010662DA}");
+        assert_eq!(
+            format!("{:?}", guid),
+            "Guid({6B29FC40-CA47-1067-B31D-00DD010662DA})"
+        );
+    }
+}
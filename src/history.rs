@@ -0,0 +1,135 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::SystemTime;
+
+use crate::{EventSink, HostedNetworkEvent};
+
+/// What happened to a peer in a `ConnectionRecord`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ConnectionKind {
+    Connected,
+    Reconnected,
+    Disconnected,
+    Rejected,
+}
+
+/// One entry in `WlanHostedNetworkHelper::connection_history()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConnectionRecord {
+    /// When the event was emitted.
+    pub at: SystemTime,
+    pub device_id: String,
+    pub kind: ConnectionKind,
+}
+
+/// Sits in front of the event sink and remembers the latest connection events, forgetting the
+/// oldest once it holds `capacity` of them.
+pub(crate) struct ConnectionHistory {
+    records: Mutex<VecDeque<ConnectionRecord>>,
+    capacity: usize,
+    sink: Arc<dyn EventSink>,
+}
+
+impl ConnectionHistory {
+    pub(crate) fn new(capacity: usize, sink: Arc<dyn EventSink>) -> Self {
+        ConnectionHistory {
+            records: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+            sink,
+        }
+    }
+
+    /// The remembered records, oldest first.
+    pub(crate) fn records(&self) -> Vec<ConnectionRecord> {
+        self.records
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    fn record(&self, device_id: &str, kind: ConnectionKind) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut records = self.records.lock().unwrap_or_else(PoisonError::into_inner);
+        if records.len() >= self.capacity {
+            records.pop_front();
+        }
+        records.push_back(ConnectionRecord {
+            at: SystemTime::now(),
+            device_id: device_id.to_string(),
+            kind,
+        });
+    }
+}
+
+impl EventSink for ConnectionHistory {
+    fn emit(&self, event: HostedNetworkEvent) {
+        match &event {
+            HostedNetworkEvent::ClientConnected { device_id, .. } => {
+                self.record(device_id, ConnectionKind::Connected)
+            }
+            HostedNetworkEvent::ClientReconnected { device_id, .. } => {
+                self.record(device_id, ConnectionKind::Reconnected)
+            }
+            HostedNetworkEvent::ClientDisconnected { device_id } => {
+                self.record(device_id, ConnectionKind::Disconnected)
+            }
+            HostedNetworkEvent::ConnectionRejected { device_id } => {
+                self.record(device_id, ConnectionKind::Rejected)
+            }
+            _ => {}
+        }
+        self.sink.emit(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConnectionHistory, ConnectionKind};
+    use crate::{EventSink, HostedNetworkEvent};
+    use std::sync::{mpsc, Arc};
+
+    #[test]
+    fn keeps_the_latest_connection_events() {
+        let (tx, rx) = mpsc::channel();
+        let history = ConnectionHistory::new(2, Arc::new(tx));
+        history.emit(HostedNetworkEvent::ConnectionRejected {
+            device_id: "WiFiDirect#5e:11:07:9a:03:c2".to_string(),
+        });
+        history.emit(HostedNetworkEvent::Started {
+            ssid: "WiFiDirectTestNetwork".to_string(),
+        });
+        history.emit(HostedNetworkEvent::ClientReconnected {
+            device_id: "WiFiDirect#02:8b:21:42:ac:36".to_string(),
+            display_name: "192.168.137.42".to_string(),
+        });
+        history.emit(HostedNetworkEvent::ClientDisconnected {
+            device_id: "WiFiDirect#02:8b:21:42:ac:36".to_string(),
+        });
+        let kinds: Vec<_> = history
+            .records()
+            .into_iter()
+            .map(|record| (record.device_id, record.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (
+                    "WiFiDirect#02:8b:21:42:ac:36".to_string(),
+                    ConnectionKind::Reconnected
+                ),
+                (
+                    "WiFiDirect#02:8b:21:42:ac:36".to_string(),
+                    ConnectionKind::Disconnected
+                ),
+            ]
+        );
+        // every event still reaches the sink
+        assert_eq!(rx.try_iter().count(), 4);
+    }
+}
@@ -0,0 +1,76 @@
+use std::io;
+use std::sync::atomic::Ordering;
+use std::sync::{PoisonError, Weak};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::sync;
+use crate::{
+    report_error, HostedNetworkEvent, HostedNetworkStatus, Shared, StopReason,
+    WlanHostedNetworkHelper,
+};
+
+/// Stops the network once it has gone `timeout` without a connected client. The clock restarts
+/// whenever a client connects. Exits once the helper shuts down or is gone. Returns `None` if the
+/// helper is already gone, or the error if the thread couldn't be started.
+pub(crate) fn spawn_idle_worker(
+    timeout: Duration,
+    shared: Weak<Shared>,
+) -> io::Result<Option<JoinHandle<()>>> {
+    let (registry, shutdown, thread_name) = match shared.upgrade() {
+        Some(shared) => (
+            shared.plumbing.clients.clone(),
+            shared.plumbing.shutdown.clone(),
+            shared.plumbing.thread_name("idle"),
+        ),
+        None => return Ok(None),
+    };
+    sync::spawn(thread_name, move || loop {
+        let clients = registry.lock();
+        let (clients, result) = if clients.is_empty() {
+            registry
+                .changed
+                .wait_timeout_while(clients, timeout, |clients| {
+                    clients.is_empty() && !shutdown.load(Ordering::SeqCst)
+                })
+                .unwrap_or_else(PoisonError::into_inner)
+        } else {
+            // check back at least once a timeout, so the worker notices the helper going away
+            registry
+                .changed
+                .wait_timeout(clients, timeout)
+                .unwrap_or_else(PoisonError::into_inner)
+        };
+        let idle = result.timed_out() && clients.is_empty();
+        drop(clients);
+        if shutdown.load(Ordering::SeqCst) {
+            return;
+        }
+        let shared = match shared.upgrade() {
+            Some(shared) => shared,
+            None => return,
+        };
+        if !idle || shared.stopped.load(Ordering::SeqCst) {
+            continue;
+        }
+        let status = shared
+            .publisher
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .status();
+        if status != Ok(HostedNetworkStatus::Started) {
+            continue;
+        }
+        debug!("No clients for {:?}, stopping hosted network", timeout);
+        let helper = WlanHostedNetworkHelper { inner: shared };
+        match helper.stop_because(StopReason::Idle) {
+            Ok(()) => helper
+                .inner
+                .plumbing
+                .events
+                .emit(HostedNetworkEvent::IdleStopped),
+            Err(error) => report_error(&*helper.inner.plumbing.events, error),
+        }
+    })
+    .map(Some)
+}
@@ -1,217 +1,1346 @@
-use std::sync::mpsc::Sender;
-use std::sync::Mutex;
-
-use windows::core::{IInspectable, Result, HSTRING};
-use windows::Devices::WiFiDirect::{
-    WiFiDirectAdvertisementPublisher, WiFiDirectAdvertisementPublisherStatus,
-    WiFiDirectAdvertisementPublisherStatusChangedEventArgs, WiFiDirectConnectionListener,
-    WiFiDirectConnectionRequestedEventArgs, WiFiDirectConnectionStatus, WiFiDirectDevice,
-    WiFiDirectError,
+#[macro_use]
+mod logging;
+
+mod access;
+mod buffer;
+mod builder;
+mod channel;
+mod client;
+mod config;
+mod connection;
+mod credentials;
+mod error;
+mod event;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod guid;
+mod history;
+mod idle;
+#[cfg(windows)]
+mod listener;
+mod manager;
+#[cfg(feature = "mock")]
+mod mock;
+mod passphrase;
+#[cfg(windows)]
+mod platform;
+mod publisher;
+mod request;
+mod retry;
+mod scan;
+mod sink;
+mod stats;
+mod status;
+mod sync;
+mod validate;
+#[cfg(windows)]
+pub mod winrt;
+
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime};
+
+use access::AccessList;
+use buffer::{BufferedSink, PollQueue};
+use builder::AdvertisementSettings;
+use client::{parse_mac, ClientRegistry, ConnectedClient, DeviceHandle, EndpointUpdate};
+use history::ConnectionHistory;
+#[cfg(windows)]
+use listener::ConnectionListener;
+use publisher::{Publisher, StatusChange};
+use request::ConnectionCallback;
+use retry::RestartSignal;
+use status::StatusCache;
+use validate::{validate_passphrase, validate_ssid};
+
+pub use access::AccessMode;
+pub use buffer::OverflowPolicy;
+pub use builder::{Discoverability, WlanHostedNetworkBuilder};
+pub use channel::{Band, ChannelInfo};
+pub use client::{ClientInfo, DeviceInfo, EndpointPair, Ipv6Endpoint};
+pub use config::HostedNetworkConfig;
+pub use credentials::Credentials;
+pub use error::{ErrorKind, HostedNetworkError, WindowsError};
+pub use event::{HostedNetworkEvent, TimedEvent};
+pub use guid::Guid;
+pub use history::{ConnectionKind, ConnectionRecord};
+pub use manager::HostedNetworkManager;
+pub use passphrase::random_passphrase;
+pub use request::{
+    ConnectionDecision, ConnectionRequestInfo, WpsMethod, CONNECTION_DECISION_TIMEOUT,
 };
-use windows::Foundation::{AsyncOperationCompletedHandler, AsyncStatus, TypedEventHandler};
-use windows::Security::Credentials::PasswordCredential;
+pub use retry::RetryPolicy;
+pub use sink::EventSink;
+pub use stats::{SignalInfo, TrafficStats};
+pub use status::{HostedNetworkStatus, StopReason};
+
+/// How many events `WlanHostedNetworkHelper::new_buffered()` keeps before dropping the oldest.
+pub const POLL_BUFFER_EVENTS: usize = 256;
+
+/// Result of the crate's fallible operations.
+pub type Result<T> = std::result::Result<T, HostedNetworkError>;
 
+/// Handle to a running hosted network. Clones share the same network, which is stopped when the
+/// last of them is dropped.
+///
+/// The handle is `Send` and `Sync`, so it can be shared between threads and held across `.await`.
+/// The WinRT objects behind it are agile, meaning usable from any thread, and are only touched
+/// under its locks.
+#[derive(Clone)]
 pub struct WlanHostedNetworkHelper {
-    publisher: Mutex<WiFiDirectAdvertisementPublisher>,
-    message_tx: Mutex<Sender<String>>, // mutex necessary for integration with tokio
+    inner: Arc<Shared>,
 }
 
+// state shared by every clone of a `WlanHostedNetworkHelper`
+struct Shared {
+    publisher: Mutex<Box<dyn Publisher>>,
+    plumbing: Plumbing,
+    settings: AdvertisementSettings,
+    credentials: Mutex<Credentials>,
+    // set by stop() so a pending auto-restart doesn't bring the network back up
+    stopped: AtomicBool,
+    // events waiting for poll_events(), if the helper was made by new_buffered()
+    poll_queue: Option<Arc<PollQueue>>,
+    // the idle and auto-restart workers, for join_workers()
+    workers: Mutex<Vec<JoinHandle<()>>>,
+    // the builder's event_buffer, whose forwarding thread join_workers() waits for
+    buffered_sink: Option<Arc<BufferedSink>>,
+    // the builder's connection_history, if it asked for one
+    history: Option<Arc<ConnectionHistory>>,
+}
+
+/// Where a publisher's callbacks report to. Every publisher the helper starts gets a clone, so a
+/// restarted network feeds the same events, listener slot and client registry.
+#[derive(Clone)]
+struct Plumbing {
+    events: Arc<dyn EventSink>,
+    // filled in by the status changed handler once the publisher has started
+    listener: Arc<Mutex<Option<ConnectionListener>>>,
+    clients: ClientRegistry,
+    connection_filter: Option<ConnectionFilter>,
+    on_connection_request: Option<ConnectionCallback>,
+    // devices allowed or denied at runtime, consulted before the filter and callback
+    access_list: Arc<Mutex<AccessList>>,
+    // set by pause_advertising(), turning every new peer away
+    paused: Arc<AtomicBool>,
+    // whether accepted peers are resolved and kept in `clients`
+    track_connections: bool,
+    // passed in the connection parameters when an accepted peer is resolved
+    #[cfg(windows)]
+    group_owner_intent: Option<u8>,
+    // peers beyond this many are turned away
+    max_clients: Option<usize>,
+    // tells the auto-restart worker, if there is one, whether each start succeeded
+    restart_tx: Option<Sender<RestartSignal>>,
+    // set once the helper is shutting down, telling the workers to exit
+    shutdown: Arc<AtomicBool>,
+    // when the publisher last reached Started, cleared once it stops or aborts
+    started_at: Arc<Mutex<Option<Instant>>>,
+    // the last status the publisher reported, for last_status() and subscribe()
+    status: Arc<Mutex<StatusCache>>,
+    // the network's span, which the WinRT callbacks run in
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    // what the crate's threads are named after, from the builder's thread_name_prefix
+    thread_prefix: Arc<str>,
+    // why the network last went down, or None while it's up, for stopped()
+    #[cfg(feature = "tokio")]
+    stop_reason: Arc<tokio::sync::watch::Sender<Option<StopReason>>>,
+}
+
+impl Plumbing {
+    /// Tells the workers to exit, waking any that are waiting.
+    fn begin_shutdown(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        self.clients.changed.notify_all();
+        if let Some(restart_tx) = &self.restart_tx {
+            let _ = restart_tx.send(RestartSignal::Shutdown);
+        }
+    }
+
+    fn is_shutting_down(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+
+    /// The name for a thread doing `role`, e.g. `wifidirect-ap-idle`.
+    fn thread_name(&self, role: &str) -> String {
+        format!("{}-{}", self.thread_prefix, role)
+    }
+
+    /// Whether `max_clients` peers are already connected.
+    fn is_full(&self) -> bool {
+        self.max_clients
+            .is_some_and(|max_clients| self.clients.lock().len() >= max_clients)
+    }
+
+    /// Runs `f` in the network's tracing span, if there is one.
+    fn in_span<R>(&self, f: impl FnOnce() -> R) -> R {
+        #[cfg(feature = "tracing")]
+        return self.span.in_scope(f);
+        #[cfg(not(feature = "tracing"))]
+        f()
+    }
+
+    /// Records the publisher's new status, and the SSID it's running under, on the span.
+    fn trace_status(&self, ssid: &str, status: HostedNetworkStatus) {
+        #[cfg(feature = "tracing")]
+        {
+            self.span.record("ssid", ssid);
+            self.span.record("status", tracing::field::display(status));
+        }
+        #[cfg(not(feature = "tracing"))]
+        let _ = (ssid, status);
+    }
+
+    /// Records a client coming or going as an event in the span.
+    fn trace_client(&self, device_id: &str, message: &'static str) {
+        #[cfg(feature = "tracing")]
+        tracing::info!(parent: &self.span, device_id, "{}", message);
+        #[cfg(not(feature = "tracing"))]
+        let _ = (device_id, message);
+    }
+
+    fn set_stop_reason(&self, reason: Option<StopReason>) {
+        #[cfg(feature = "tokio")]
+        self.stop_reason.send_replace(reason);
+        #[cfg(not(feature = "tokio"))]
+        let _ = reason;
+    }
+
+    /// Closes every connected client, removing its status handler, and reports it as
+    /// disconnected. Used whenever the network goes down, so no handlers or devices outlive it.
+    fn drop_clients(&self) {
+        let stale: Vec<ConnectedClient> = self
+            .clients
+            .lock()
+            .drain()
+            .map(|(_, client)| client)
+            .collect();
+        self.clients.changed.notify_all();
+        for client in stale {
+            if let Err(error) = client.close() {
+                debug!("Couldn't close client {}: {}", client.device_id, error);
+            }
+            self.trace_client(&client.device_id, "client disconnected");
+            self.events.emit(HostedNetworkEvent::ClientDisconnected {
+                device_id: client.device_id,
+            });
+        }
+    }
+
+    fn set_started_at(&self, started_at: Option<Instant>) {
+        *self
+            .started_at
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = started_at;
+    }
+}
+
+/// Without Windows there is no listener, so the slot for one stays empty.
+#[cfg(not(windows))]
+enum ConnectionListener {}
+
 impl WlanHostedNetworkHelper {
     pub fn new(
         ssid: &str,
         password: &str,
-        message_tx: Sender<String>,
+        message_tx: Sender<HostedNetworkEvent>,
         success_tx: Sender<bool>,
     ) -> Result<Self> {
-        let publisher = start(ssid, password, message_tx.clone(), success_tx.clone())?;
-        Ok(WlanHostedNetworkHelper {
-            publisher: Mutex::new(publisher),
-            message_tx: Mutex::new(message_tx),
+        WlanHostedNetworkBuilder::from(HostedNetworkConfig::new(ssid, password))
+            .event_sender(message_tx)
+            .success_sender(success_tx)
+            .build()
+    }
+
+    /// Starts the hosted network described by `config`, sending its events to `message_tx`.
+    pub fn with_config(
+        config: HostedNetworkConfig,
+        message_tx: Sender<HostedNetworkEvent>,
+    ) -> Result<Self> {
+        WlanHostedNetworkBuilder::from(config)
+            .event_sender(message_tx)
+            .build()
+    }
+
+    /// Like `new`, but waits up to `timeout` for the network to come up and returns the
+    /// publisher's error if it aborts instead, so a failed start can be handled right here. See
+    /// `WlanHostedNetworkBuilder::build_checked()`.
+    pub fn new_checked(
+        ssid: &str,
+        password: &str,
+        message_tx: Sender<HostedNetworkEvent>,
+        timeout: Duration,
+    ) -> Result<Self> {
+        WlanHostedNetworkBuilder::new()
+            .ssid(ssid)
+            .passphrase(password)
+            .event_sender(message_tx)
+            .build_checked(timeout)
+    }
+
+    /// Like `new_checked`, but waits until the network comes up, fails, or `cancel` is set, in
+    /// which case the half-started network is torn down and `HostedNetworkError::Canceled` is
+    /// returned. See `WlanHostedNetworkBuilder::build_cancellable()`.
+    pub fn start_cancellable(
+        ssid: &str,
+        password: &str,
+        message_tx: Sender<HostedNetworkEvent>,
+        cancel: &AtomicBool,
+    ) -> Result<Self> {
+        WlanHostedNetworkBuilder::new()
+            .ssid(ssid)
+            .passphrase(password)
+            .event_sender(message_tx)
+            .build_cancellable(cancel)
+    }
+
+    /// Starts the hosted network with its events kept in an internal buffer, to be collected
+    /// with `poll_events()` instead of received from a channel. The buffer holds the latest
+    /// `POLL_BUFFER_EVENTS` events; older ones are dropped if it isn't polled often enough.
+    pub fn new_buffered(ssid: &str, password: &str) -> Result<Self> {
+        let queue = Arc::new(PollQueue::new(POLL_BUFFER_EVENTS));
+        let sink_queue = queue.clone();
+        let mut builder = WlanHostedNetworkBuilder::new();
+        builder
+            .ssid(ssid)
+            .passphrase(password)
+            .event_sink(move |event| sink_queue.push(event));
+        builder.poll_queue = Some(queue);
+        builder.build()
+    }
+
+    /// Reports whether this PC looks able to host a network: it has a Wi-Fi adapter and Windows
+    /// can create a Wi-Fi Direct advertisement publisher. Nothing is advertised. A driver without
+    /// Wi-Fi Direct support can still pass this check and abort with `NoCapableAdapter` on start.
+    /// Always `Ok(false)` on other platforms.
+    pub fn is_supported() -> Result<bool> {
+        scan::is_supported()
+    }
+
+    /// Starts the hosted network and resolves once Windows reports it as started, or with the
+    /// reason it couldn't start if the publisher aborts. The blocking WinRT calls run on tokio's
+    /// blocking thread pool.
+    #[cfg(feature = "tokio")]
+    pub async fn start_async(
+        ssid: &str,
+        password: &str,
+        message_tx: Sender<HostedNetworkEvent>,
+    ) -> Result<Self> {
+        let (started_tx, started_rx) = tokio::sync::oneshot::channel();
+        let mut started_tx = Some(started_tx);
+        let mut builder = WlanHostedNetworkBuilder::new();
+        builder
+            .ssid(ssid)
+            .passphrase(password)
+            .event_sender(message_tx);
+        let helper = tokio::task::spawn_blocking(move || {
+            builder.build_with(Some(Box::new(move |result| {
+                if let Some(started_tx) = started_tx.take() {
+                    let _ = started_tx.send(result);
+                }
+            })))
         })
+        .await
+        .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))?;
+        // the sender is only dropped without firing if the publisher goes away first
+        started_rx
+            .await
+            .unwrap_or(Err(HostedNetworkError::ChannelClosed))?;
+        Ok(helper)
+    }
+
+    /// Starts the hosted network and returns it along with a stream of its events, for async code
+    /// that would rather not block a thread on an `mpsc::Receiver`. Like `new`, this returns once
+    /// the publisher has been asked to start; `Started` or `Aborted` arrives on the stream.
+    #[cfg(feature = "tokio")]
+    pub fn new_with_stream(
+        ssid: &str,
+        password: &str,
+    ) -> Result<(Self, impl tokio_stream::Stream<Item = HostedNetworkEvent>)> {
+        let (stream_tx, stream_rx) = tokio::sync::mpsc::unbounded_channel();
+        let helper = WlanHostedNetworkBuilder::new()
+            .ssid(ssid)
+            .passphrase(password)
+            // sending on an unbounded channel never blocks the callback thread
+            .event_sink(move |event| {
+                let _ = stream_tx.send(event);
+            })
+            .build()?;
+        Ok((
+            helper,
+            tokio_stream::wrappers::UnboundedReceiverStream::new(stream_rx),
+        ))
+    }
+
+    /// Stops the network if it is running and starts it again under a new SSID and passphrase.
+    /// Connected clients are disconnected, and observers see `Stopped` followed by `Started`.
+    pub fn restart(&self, ssid: &str, password: &str) -> Result<()> {
+        validate_ssid(ssid)?;
+        self.restart_with(Some(ssid), password)
+    }
+
+    /// Switches the network to a new passphrase under the same SSID. Windows only reads the legacy
+    /// settings when the publisher starts, so this restarts the network like `restart()`: every
+    /// connected client is disconnected and has to rejoin with the new passphrase. A network
+    /// built without an SSID keeps the one Windows generated for it, or gets a new one if it
+    /// never started.
+    pub fn set_passphrase(&self, password: &str) -> Result<()> {
+        self.restart_with(None, password)
+    }
+
+    /// Restarts the network with `password` under `ssid`, or with `None`, under the SSID it has
+    /// now.
+    fn restart_with(&self, ssid: Option<&str>, password: &str) -> Result<()> {
+        validate_passphrase(password)?;
+        let mut publisher = sync::lock(&self.inner.publisher, "publisher");
+        let ssid = match ssid {
+            Some(ssid) => ssid.to_string(),
+            None => {
+                let given = sync::lock(&self.inner.credentials, "credentials")
+                    .ssid
+                    .clone();
+                // empty until Windows has generated one, which leaves it to generate another
+                if given.is_empty() {
+                    publisher.ssid().unwrap_or_default()
+                } else {
+                    given
+                }
+            }
+        };
+        let ssid = ssid.as_str();
+        if publisher.status()? == HostedNetworkStatus::Started {
+            publisher.stop()?;
+        }
+        sync::lock(&self.inner.plumbing.listener, "listener").take();
+        // the old clients belonged to the old network, so forget them
+        self.inner.plumbing.drop_clients();
+        // start a fresh publisher, like the C++ sample does, with the same event plumbing
+        *publisher = start(
+            ssid,
+            password,
+            &self.inner.settings,
+            self.inner.plumbing.clone(),
+            None,
+        )?;
+        self.inner.stopped.store(false, Ordering::SeqCst);
+        *sync::lock(&self.inner.credentials, "credentials") = Credentials {
+            ssid: ssid.to_string(),
+            passphrase: password.to_string(),
+        };
+        Ok(())
+    }
+
+    /// Returns the network's current SSID and passphrase. They can change under `restart()` and
+    /// `set_passphrase()`, so this is a copy rather than a borrow.
+    pub fn credentials(&self) -> Credentials {
+        let credentials = sync::lock(&self.inner.credentials, "credentials").clone();
+        if !credentials.ssid.is_empty() {
+            return credentials;
+        }
+        // Windows chose the SSID, so ask the publisher, without holding the credentials lock
+        // since restart() takes the two the other way round
+        let generated = match self.inner.publisher.lock() {
+            Ok(publisher) => publisher.ssid().unwrap_or_default(),
+            Err(_) => String::new(),
+        };
+        let mut stored = sync::lock(&self.inner.credentials, "credentials");
+        if stored.ssid.is_empty() {
+            stored.ssid = generated;
+        }
+        stored.clone()
+    }
+
+    /// Returns the network's current SSID. If none was given, this is the one Windows generated,
+    /// or empty until the network has started.
+    pub fn ssid(&self) -> String {
+        self.credentials().ssid
+    }
+
+    /// Returns the network's current passphrase. Prefer `credentials()` where it helps to make
+    /// reading the secret stand out.
+    pub fn passphrase(&self) -> String {
+        self.credentials().passphrase
+    }
+
+    /// Returns a `WIFI:T:WPA;S:<ssid>;P:<passphrase>;;` string for the network, which phones can
+    /// join from when it's rendered as a QR code.
+    pub fn connection_uri(&self) -> Result<String> {
+        Ok(self.credentials().uri())
+    }
+
+    /// Takes the events buffered since the last call, oldest first, led by `EventsDropped` if the
+    /// buffer overflowed in between. Always empty unless the helper was made by `new_buffered()`.
+    pub fn poll_events(&self) -> Vec<HostedNetworkEvent> {
+        match &self.inner.poll_queue {
+            Some(queue) => queue.drain(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns the current state of the publisher.
+    pub fn status(&self) -> Result<HostedNetworkStatus> {
+        let publisher = sync::lock(&self.inner.publisher, "publisher");
+        publisher.status()
+    }
+
+    /// Returns the last status the publisher reported, without asking Windows. Unlike `status()`
+    /// it can't fail, and it is already up to date when the matching event arrives, so a caller
+    /// that set up its event handling late can still tell whether it missed `Started`.
+    pub fn last_status(&self) -> HostedNetworkStatus {
+        sync::lock(&self.inner.plumbing.status, "status").last
+    }
+
+    /// Returns a channel that receives `last_status()` straight away and then every status the
+    /// publisher reports after it, including across `restart()`. Nothing is missed between the
+    /// two, however late the call. The channel is dropped from the helper once its receiver is.
+    pub fn subscribe(&self) -> Receiver<HostedNetworkStatus> {
+        sync::lock(&self.inner.plumbing.status, "status").subscribe()
+    }
+
+    /// Whether the network is up, i.e. `status()` is `Started`. Anything else, including a
+    /// status that can't be read or a poisoned lock, counts as not running.
+    pub fn is_running(&self) -> bool {
+        match self.inner.publisher.lock() {
+            Ok(publisher) => publisher.status() == Ok(HostedNetworkStatus::Started),
+            Err(_) => false,
+        }
+    }
+
+    /// Returns the band and channel the hosted network is operating on.
+    ///
+    /// Always `Ok(None)` for now: the legacy AP API doesn't report the channel. Neither the
+    /// publisher nor `WiFiDirectDevice` exposes it, and `WiFiAdapter` only reports channels for
+    /// networks it has scanned, which doesn't include the one it is hosting. The method exists so
+    /// callers can ask without a breaking change if a later Windows release reveals it.
+    pub fn operating_channel(&self) -> Result<Option<ChannelInfo>> {
+        Ok(None)
+    }
+
+    /// The fake publisher behind this helper, for tests to drive.
+    #[cfg(all(test, feature = "mock"))]
+    pub(crate) fn mock_publisher(&self) -> mock::MockPublisher {
+        sync::lock(&self.inner.publisher, "publisher")
+            .as_mock()
+            .expect("every publisher is a mock with the mock feature")
+            .clone()
+    }
+
+    /// Returns the connects, reconnects, disconnects and rejections seen so far, oldest first,
+    /// up to the limit given to the builder's `connection_history()`. Always empty unless that
+    /// was set.
+    pub fn connection_history(&self) -> Vec<ConnectionRecord> {
+        match &self.inner.history {
+            Some(history) => history.records(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns the device IDs of the peers currently connected to the hosted network.
+    pub fn connected_clients(&self) -> Vec<String> {
+        self.inner
+            .plumbing
+            .clients
+            .lock()
+            .values()
+            .map(|client| client.device_id.clone())
+            .collect()
+    }
+
+    /// Blocks until at least one peer is connected, then returns the device IDs of the connected
+    /// peers. Returns straight away if any are already connected, or
+    /// `HostedNetworkError::Timeout` if none connect within `timeout`.
+    pub fn wait_for_client(&self, timeout: Duration) -> Result<Vec<String>> {
+        let registry = &self.inner.plumbing.clients;
+        let clients = registry.lock();
+        let (clients, _) = registry
+            .changed
+            .wait_timeout_while(clients, timeout, |clients| clients.is_empty())
+            .unwrap_or_else(PoisonError::into_inner);
+        if clients.is_empty() {
+            return Err(HostedNetworkError::Timeout);
+        }
+        Ok(clients
+            .values()
+            .map(|client| client.device_id.clone())
+            .collect())
+    }
+
+    /// Blocks until the peer with the given device ID is connected, e.g. one known from an earlier
+    /// pairing, and returns it. Returns straight away if it already is, or
+    /// `HostedNetworkError::Timeout` if it doesn't connect within `timeout`. The other per-client
+    /// methods take the same ID, so `client_endpoints()` and the like can follow.
+    pub fn wait_for_client_id(&self, device_id: &str, timeout: Duration) -> Result<ClientInfo> {
+        let registry = &self.inner.plumbing.clients;
+        let clients = registry.lock();
+        let (clients, _) = registry
+            .changed
+            .wait_timeout_while(clients, timeout, |clients| !clients.contains_key(device_id))
+            .unwrap_or_else(PoisonError::into_inner);
+        clients
+            .get(device_id)
+            .map(ConnectedClient::snapshot)
+            .ok_or(HostedNetworkError::Timeout)
+    }
+
+    /// Returns how long the network has been up, or `None` if it isn't running. The clock starts
+    /// again whenever the network is restarted.
+    pub fn uptime(&self) -> Option<Duration> {
+        sync::lock(&self.inner.plumbing.started_at, "start time")
+            .map(|started_at| started_at.elapsed())
+    }
+
+    /// Returns how long the peer with the given device ID has been connected, or `None` if it
+    /// isn't.
+    pub fn client_uptime(&self, device_id: &str) -> Option<Duration> {
+        self.inner
+            .plumbing
+            .clients
+            .lock()
+            .get(device_id)
+            .map(|client| client.connected_at.elapsed())
+    }
+
+    /// Whether the peer with exactly this device ID is connected.
+    pub fn is_client_connected(&self, device_id: &str) -> bool {
+        self.inner.plumbing.clients.lock().contains_key(device_id)
+    }
+
+    /// Returns the number of peers currently connected to the hosted network.
+    pub fn client_count(&self) -> usize {
+        self.inner.plumbing.clients.lock().len()
+    }
+
+    /// Returns the IP addresses the peer with the given device ID can be reached at. The client
+    /// map is only locked to look the peer up, so calls for different peers, from different
+    /// threads, run side by side.
+    pub fn client_endpoints(&self, device_id: &str) -> Result<Vec<IpAddr>> {
+        self.client_device(device_id)?.remote_addresses()
+    }
+
+    /// Reads the peer's IP addresses again, for when it may have renewed its DHCP lease or moved
+    /// to another address mid-session. If they differ from the ones last reported, in
+    /// `ClientReady` or an earlier `ClientEndpointsChanged`, `ClientEndpointsChanged` is sent with
+    /// the new ones. A peer that had no addresses yet gets `ClientReady` instead, the same event
+    /// it would have been sent had its first address turned up while the crate was still
+    /// checking. A peer that disconnects while this runs is reported as `UnknownClient`.
+    pub fn refresh_client_endpoints(&self, device_id: &str) -> Result<Vec<IpAddr>> {
+        let endpoints = self.client_device(device_id)?.remote_addresses()?;
+        let plumbing = &self.inner.plumbing;
+        let device_id = device_id.to_string();
+        let event = match plumbing.clients.update_endpoints(&device_id, &endpoints) {
+            None => return Err(HostedNetworkError::UnknownClient(device_id)),
+            Some(EndpointUpdate::Unchanged) => return Ok(endpoints),
+            Some(EndpointUpdate::First) => HostedNetworkEvent::ClientReady {
+                device_id,
+                endpoints: endpoints.clone(),
+            },
+            Some(EndpointUpdate::Changed) => HostedNetworkEvent::ClientEndpointsChanged {
+                device_id,
+                endpoints: endpoints.clone(),
+            },
+        };
+        plumbing.events.emit(event);
+        Ok(endpoints)
+    }
+
+    /// Returns every connected peer's device ID along with the IP addresses it can be reached
+    /// at. Peers whose addresses can't be read right now are left out.
+    pub fn all_client_endpoints(&self) -> Result<Vec<(String, Vec<IpAddr>)>> {
+        let devices = self.inner.plumbing.clients.devices();
+        let mut endpoints = Vec::with_capacity(devices.len());
+        for (device_id, device) in devices {
+            match device.remote_addresses() {
+                Ok(addresses) => endpoints.push((device_id, addresses)),
+                Err(error) => debug!("Couldn't read endpoints of {}: {}", device_id, error),
+            }
+        }
+        Ok(endpoints)
+    }
+
+    /// Returns a socket address for `port` at every address of every connected peer, paired with
+    /// the peer's device ID, e.g. for sending to them all. A peer reachable over both IPv4 and
+    /// IPv6 appears once for each, and IPv6 addresses keep their zone. Peers with no address yet,
+    /// or whose addresses can't be read, are left out.
+    pub fn client_socket_addrs(&self, port: u16) -> Result<Vec<(String, SocketAddr)>> {
+        let mut socket_addrs = Vec::new();
+        for (device_id, device) in self.inner.plumbing.clients.devices() {
+            match device.remote_socket_addrs(port) {
+                Ok(addrs) if addrs.is_empty() => debug!("{} has no address yet", device_id),
+                Ok(addrs) => {
+                    socket_addrs.extend(addrs.into_iter().map(|addr| (device_id.clone(), addr)))
+                }
+                Err(error) => debug!("Couldn't read endpoints of {}: {}", device_id, error),
+            }
+        }
+        Ok(socket_addrs)
+    }
+
+    /// Returns the IPv4 addresses the peer with the given device ID can be reached at.
+    pub fn client_ipv4_endpoints(&self, device_id: &str) -> Result<Vec<Ipv4Addr>> {
+        Ok(self
+            .client_endpoints(device_id)?
+            .into_iter()
+            .filter_map(|address| match address {
+                IpAddr::V4(address) => Some(address),
+                IpAddr::V6(_) => None,
+            })
+            .collect())
+    }
+
+    /// Returns the IPv6 addresses the peer with the given device ID can be reached at. Unlike
+    /// `client_endpoints()`, these keep the zone Windows reports, which link-local addresses
+    /// need to be usable.
+    pub fn client_ipv6_endpoints(&self, device_id: &str) -> Result<Vec<Ipv6Endpoint>> {
+        self.client_device(device_id)?.remote_ipv6_endpoints()
+    }
+
+    /// Returns every local/remote address pair Windows reports for the peer with the given device
+    /// ID, typically one per address family. The order is whatever Windows reports and isn't
+    /// guaranteed to be stable between calls. Pairs whose host names aren't IP addresses are
+    /// skipped.
+    pub fn client_endpoint_pairs(&self, device_id: &str) -> Result<Vec<EndpointPair>> {
+        self.client_device(device_id)?.endpoint_pairs()
+    }
+
+    /// Returns the MAC address of the peer with the given device ID, read from the ID Windows
+    /// assigns it. Phones usually join with a randomized, per-network MAC (the locally
+    /// administered bit, `0x02` in the first octet, is set), which stays the same for this
+    /// network but isn't the device's hardware address and can change if the peer forgets the
+    /// network. Returns `HostedNetworkError::MacUnavailable` if the ID doesn't contain one.
+    pub fn client_mac(&self, device_id: &str) -> Result<[u8; 6]> {
+        let clients = self.inner.plumbing.clients.lock();
+        if !clients.contains_key(device_id) {
+            return Err(HostedNetworkError::UnknownClient(device_id.to_string()));
+        }
+        parse_mac(device_id)
+            .ok_or_else(|| HostedNetworkError::MacUnavailable(device_id.to_string()))
+    }
+
+    /// Returns this PC's address on the hosted network, for binding servers that peers should
+    /// reach. Windows doesn't report it directly, so it is read from the local side of a
+    /// connected client's endpoint pairs, preferring IPv4. Until a client has connected this
+    /// returns `HostedNetworkError::LocalAddressUnavailable`.
+    pub fn local_address(&self) -> Result<IpAddr> {
+        let mut fallback = None;
+        for (_, device) in self.inner.plumbing.clients.devices() {
+            for address in device.local_addresses()? {
+                if address.is_ipv4() {
+                    return Ok(address);
+                }
+                fallback.get_or_insert(address);
+            }
+        }
+        fallback.ok_or(HostedNetworkError::LocalAddressUnavailable)
+    }
+
+    /// Binds a `TcpListener` to `local_address()` on `port`, for running a server that only
+    /// peers on the hosted network can reach. Pass port 0 to let Windows pick one. The address
+    /// is only known once a client has connected, so until then this fails with
+    /// `io::ErrorKind::AddrNotAvailable`; wait for `ClientReady` and try again.
+    pub fn bind_tcp(&self, port: u16) -> io::Result<TcpListener> {
+        let address = self.local_address().map_err(|error| match error {
+            HostedNetworkError::LocalAddressUnavailable => io::Error::new(
+                io::ErrorKind::AddrNotAvailable,
+                "the hosted network's address is unknown until a client connects, \
+                 so wait for ClientReady",
+            ),
+            error => io::Error::other(error),
+        })?;
+        TcpListener::bind((address, port))
+    }
+
+    /// Returns the interface GUID of the virtual adapter Windows creates for the hosted network,
+    /// e.g. for scoping a firewall rule to it. The adapter only exists while the network is
+    /// `Started`, and Windows only reveals which adapter it is through a connected client, so
+    /// until one connects this returns `HostedNetworkError::AdapterUnavailable`.
+    pub fn interface_guid(&self) -> Result<Guid> {
+        for (_, device) in self.inner.plumbing.clients.devices() {
+            if let Some(guid) = device.adapter_id()? {
+                return Ok(guid);
+            }
+        }
+        Err(HostedNetworkError::AdapterUnavailable)
+    }
+
+    /// Would return how much traffic the peer with the given device ID has sent and received,
+    /// but Windows doesn't attribute traffic on the hosted network to individual peers, so this
+    /// is always `Ok(None)` for a connected peer. Use `interface_stats()` for the totals.
+    /// Returns `HostedNetworkError::UnknownClient` if no such peer is connected.
+    pub fn client_stats(&self, device_id: &str) -> Result<Option<TrafficStats>> {
+        if !self.is_client_connected(device_id) {
+            return Err(HostedNetworkError::UnknownClient(device_id.to_string()));
+        }
+        Ok(None)
+    }
+
+    /// Would return the signal strength and link quality of the peer with the given device ID,
+    /// but Windows doesn't report either for stations on the hosted network: `WiFiDirectDevice`
+    /// has no signal information, and the `WiFiAdapter` network report and connection profile
+    /// signal bars describe networks this PC could join, not peers that joined it. So this is
+    /// always `Ok(None)` for a connected peer, and `SignalInfo` is there for when that changes.
+    /// Returns `HostedNetworkError::UnknownClient` if no such peer is connected.
+    pub fn client_signal(&self, device_id: &str) -> Result<Option<SignalInfo>> {
+        if !self.is_client_connected(device_id) {
+            return Err(HostedNetworkError::UnknownClient(device_id.to_string()));
+        }
+        Ok(None)
+    }
+
+    /// Returns the bytes sent and received on the hosted network's adapter since the network
+    /// last started, summed over every peer. Windows records this usage per connection profile
+    /// and updates it lazily, so the figures are rough and can trail recent transfers by a few
+    /// minutes. Returns `Ok(None)` while the network isn't running, until a client has connected
+    /// (see `interface_guid()`), or if Windows keeps no profile for the adapter.
+    pub fn interface_stats(&self) -> Result<Option<TrafficStats>> {
+        let uptime = match self.uptime() {
+            Some(uptime) => uptime,
+            None => return Ok(None),
+        };
+        let adapter_id = match self.interface_guid() {
+            Ok(adapter_id) => adapter_id,
+            Err(HostedNetworkError::AdapterUnavailable) => return Ok(None),
+            Err(error) => return Err(error),
+        };
+        stats::adapter_usage(adapter_id, SystemTime::now() - uptime)
     }
 
+    /// Same as `interface_guid()`, formatted the way Windows tools expect, e.g.
+    /// `{6B29FC40-CA47-1067-B31D-00DD010662DA}`.
+    pub fn interface_guid_string(&self) -> Result<String> {
+        Ok(self.interface_guid()?.to_string())
+    }
+
+    /// Disconnects the peer with the given device ID and removes it from the connected clients.
+    /// Returns `HostedNetworkError::UnknownClient` if no such peer is connected, including one
+    /// that disconnected on its own just before this call.
+    pub fn disconnect_client(&self, device_id: &str) -> Result<()> {
+        // Removing it under the lock means the connection status changed handler won't also
+        // report this client as disconnected.
+        let client = self
+            .inner
+            .plumbing
+            .clients
+            .lock()
+            .remove(device_id)
+            .ok_or_else(|| HostedNetworkError::UnknownClient(device_id.to_string()))?;
+        self.inner.plumbing.clients.changed.notify_all();
+        let closed = client.close().or_else(|error| {
+            // closing a device that has already dropped off isn't worth failing over
+            if client.device.is_connected()? {
+                Err(error)
+            } else {
+                Ok(())
+            }
+        });
+        // it's out of the registry either way, so it's reported even if tearing it down failed
+        self.inner
+            .plumbing
+            .events
+            .emit(HostedNetworkEvent::ClientDisconnected {
+                device_id: client.device_id,
+            });
+        closed
+    }
+
+    /// The most peers the network lets connect at once, as set by the builder's
+    /// `max_clients()`. Windows doesn't report its own limit, so without that this is `None`.
+    pub fn max_clients(&self) -> Option<usize> {
+        self.inner.plumbing.max_clients
+    }
+
+    /// Stops accepting new peers while keeping the connected ones, e.g. for a maintenance window.
+    /// Sends `AdvertisingPaused` unless already paused.
+    ///
+    /// Windows can't stop advertising without tearing down the group, and every client with it,
+    /// so the network stays visible and new peers are turned away with `ConnectionRejected`
+    /// instead, as by the builder's `connection_filter()`. The same caveat about the 802.11 link
+    /// applies.
+    pub fn pause_advertising(&self) -> Result<()> {
+        if !self.inner.plumbing.paused.swap(true, Ordering::SeqCst) {
+            self.inner
+                .plumbing
+                .events
+                .emit(HostedNetworkEvent::AdvertisingPaused);
+        }
+        Ok(())
+    }
+
+    /// Accepts new peers again after `pause_advertising()`, sending `AdvertisingResumed` unless
+    /// not paused.
+    pub fn resume_advertising(&self) -> Result<()> {
+        if self.inner.plumbing.paused.swap(false, Ordering::SeqCst) {
+            self.inner
+                .plumbing
+                .events
+                .emit(HostedNetworkEvent::AdvertisingResumed);
+        }
+        Ok(())
+    }
+
+    /// Lets the peer with the given device ID connect, taking it off the deny list. In
+    /// `AccessMode::AllowList` mode only peers allowed this way can connect.
+    pub fn allow_device(&self, device_id: &str) {
+        sync::lock(&self.inner.plumbing.access_list, "access list").allow(device_id);
+    }
+
+    /// Turns the peer with the given device ID away from now on, taking it off the allow list.
+    /// Its connection requests are answered with `ConnectionRejected`, and if it's connected it
+    /// is disconnected as by `disconnect_client()`.
+    pub fn deny_device(&self, device_id: &str) -> Result<()> {
+        sync::lock(&self.inner.plumbing.access_list, "access list").deny(device_id);
+        self.enforce_access_list()
+    }
+
+    /// Switches the access list between allowing only the listed peers and denying only the
+    /// listed ones. Connected peers the new mode doesn't permit are disconnected.
+    pub fn set_access_mode(&self, mode: AccessMode) -> Result<()> {
+        sync::lock(&self.inner.plumbing.access_list, "access list").mode = mode;
+        self.enforce_access_list()
+    }
+
+    /// Returns whether the access list is allowing or denying the peers on it.
+    pub fn access_mode(&self) -> AccessMode {
+        sync::lock(&self.inner.plumbing.access_list, "access list").mode
+    }
+
+    // disconnects every connected client the access list no longer permits
+    fn enforce_access_list(&self) -> Result<()> {
+        let refused: Vec<String> = {
+            let access_list = sync::lock(&self.inner.plumbing.access_list, "access list");
+            self.connected_clients()
+                .into_iter()
+                .filter(|device_id| !access_list.permits(device_id))
+                .collect()
+        };
+        for device_id in refused {
+            match self.disconnect_client(&device_id) {
+                // it left on its own in the meantime
+                Err(HostedNetworkError::UnknownClient(_)) => {}
+                result => result?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Stops accepting new clients, waits up to `grace` for the connected ones to leave, then
+    /// stops the network as `stop()` does.
+    ///
+    /// Unlike `stop()`, which drops every client at once, this lets in-flight transfers finish.
+    /// Connection requests that arrive during the grace period are ignored, and each client that
+    /// leaves is reported with `ClientDisconnected` as usual. Clients still connected when `grace`
+    /// runs out are disconnected by the stop.
+    pub fn stop_graceful(&self, grace: Duration) -> Result<()> {
+        // no auto-restart while draining, and no listener to accept new joins
+        self.inner.stopped.store(true, Ordering::SeqCst);
+        sync::lock(&self.inner.plumbing.listener, "listener").take();
+        let registry = &self.inner.plumbing.clients;
+        let clients = registry.lock();
+        let (clients, _) = registry
+            .changed
+            .wait_timeout_while(clients, grace, |clients| !clients.is_empty())
+            .unwrap_or_else(PoisonError::into_inner);
+        drop(clients);
+        self.stop()
+    }
+
+    /// Stops the network for every clone of this helper. Calling it again once the network has
+    /// stopped is harmless and sends `NotRunning`.
     pub fn stop(&self) -> Result<()> {
+        self.stop_because(StopReason::Stopped)
+    }
+
+    /// Tears everything down in order and consumes the helper, for the end of its life rather
+    /// than a pause. The network is stopped if it's running; then the handlers registered with
+    /// Windows for status changes, connection requests and each client are removed, any
+    /// `stopped()` futures resolve, and the background threads are joined as by
+    /// `join_workers()`. All of it has happened by the time this returns.
+    ///
+    /// `stop()` takes `&self` so a helper shared between threads can be stopped and restarted.
+    /// Taking `self` here means this handle can't be used afterwards. Other clones can, but find
+    /// the network stopped for good.
+    pub fn shutdown(self) -> Result<()> {
+        let result = if self.is_running() {
+            self.stop()
+        } else {
+            Ok(())
+        };
+        self.inner.stopped.store(true, Ordering::SeqCst);
+        // after stopping, so the Stopped change still arrives
+        let unsubscribed = sync::lock(&self.inner.publisher, "publisher").unsubscribe();
+        sync::lock(&self.inner.plumbing.listener, "listener").take();
+        self.inner.plumbing.drop_clients();
+        #[cfg(feature = "tokio")]
+        self.inner.plumbing.stop_reason.send_if_modified(|reason| {
+            let unresolved = reason.is_none();
+            reason.get_or_insert(StopReason::Stopped);
+            unresolved
+        });
+        let joined = self.join_workers();
+        result.and(unsubscribed).and(joined)
+    }
+
+    /// Stops the network if it's running, then shuts down the helper's background threads and
+    /// waits for them to exit, so none outlive it. Use it as the last call on a helper: the
+    /// network can still be restarted afterwards, but without its idle timeout or auto-restart.
+    ///
+    /// The threads it waits for are the `auto_restart()` worker, which may first finish sleeping
+    /// out its backoff; the `idle_timeout()` worker; and the `event_buffer()` thread, which first
+    /// delivers any events still queued. Events sent after that are dropped. Without this, each
+    /// of them exits on its own once the last clone of the helper is dropped. Threads started to
+    /// check a new client's address or run `on_connection_request()` aren't tracked, and exit
+    /// within ten seconds.
+    pub fn join_workers(&self) -> Result<()> {
+        let result = if self.is_running() {
+            self.stop()
+        } else {
+            Ok(())
+        };
+        self.inner.plumbing.begin_shutdown();
+        let workers = std::mem::take(&mut *sync::lock(&self.inner.workers, "workers"));
+        for worker in workers {
+            // a panicking worker has already been reported by the panic hook
+            let _ = worker.join();
+        }
+        if let Some(buffered_sink) = &self.inner.buffered_sink {
+            buffered_sink.join();
+        }
+        result
+    }
+
+    /// Resolves once the network goes down, whether through `stop()`, the idle timeout or an
+    /// abort, or straight away if it's already down. A restart puts it back up, so a future
+    /// made afterwards waits for the next time.
+    #[cfg(feature = "tokio")]
+    pub fn stopped(&self) -> impl std::future::Future<Output = StopReason> {
+        let mut reasons = self.inner.plumbing.stop_reason.subscribe();
+        async move {
+            loop {
+                if let Some(reason) = reasons.borrow_and_update().clone() {
+                    return reason;
+                }
+                // the sender only goes away with the last clone of the helper, which stops it
+                if reasons.changed().await.is_err() {
+                    return StopReason::Stopped;
+                }
+            }
+        }
+    }
+
+    fn stop_because(&self, reason: StopReason) -> Result<()> {
+        let publisher = sync::lock(&self.inner.publisher, "publisher");
+        self.inner.stopped.store(true, Ordering::SeqCst);
+        let status = publisher.status()?;
+        if status == HostedNetworkStatus::Started {
+            publisher.stop()?;
+            self.inner.plumbing.set_stop_reason(Some(reason));
+        } else {
+            self.inner
+                .plumbing
+                .events
+                .emit(HostedNetworkEvent::NotRunning);
+        }
+        // deregister the connection requested handler
+        sync::lock(&self.inner.plumbing.listener, "listener").take();
+        self.inner.plumbing.drop_clients();
+        Ok(())
+    }
+
+    /// Looks up a connected client, for reading its endpoints without holding the client map.
+    fn client_device(&self, device_id: &str) -> Result<DeviceHandle> {
+        self.inner
+            .plumbing
+            .clients
+            .device(device_id)
+            .ok_or_else(|| HostedNetworkError::UnknownClient(device_id.to_string()))
+    }
+
+    /// Tears down a network whose start was canceled. The status handler goes first, so a
+    /// `Started` that was still on its way can't bring up the listener afterwards, and nothing
+    /// is left registered with Windows that would keep the plumbing alive.
+    pub(crate) fn cancel_start(&self) {
+        self.inner.stopped.store(true, Ordering::SeqCst);
+        {
+            let publisher = self
+                .inner
+                .publisher
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            let _ = publisher.unsubscribe();
+            // it may be started, or still on its way there
+            let _ = publisher.stop();
+        }
+        self.inner
+            .plumbing
+            .listener
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        self.inner.plumbing.drop_clients();
+        self.inner.plumbing.set_started_at(None);
+        self.inner.plumbing.begin_shutdown();
+    }
+}
+
+impl fmt::Debug for WlanHostedNetworkHelper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // try_lock, because the thread being debugged may already hold one of these
+        const BUSY: &str = "<locked>";
+        let status = match self.inner.publisher.try_lock() {
+            Ok(publisher) => match publisher.status() {
+                Ok(status) => status.to_string(),
+                Err(e) => e.to_string(),
+            },
+            Err(_) => BUSY.to_string(),
+        };
+        let ssid = match self.inner.credentials.try_lock() {
+            Ok(credentials) => credentials.ssid.clone(),
+            Err(_) => BUSY.to_string(),
+        };
+        let clients = match self.inner.plumbing.clients.try_lock() {
+            Ok(clients) => clients.len().to_string(),
+            Err(_) => BUSY.to_string(),
+        };
+        f.debug_struct("WlanHostedNetworkHelper")
+            .field("status", &format_args!("{}", status))
+            .field("ssid", &ssid)
+            .field("passphrase", &"<redacted>")
+            .field("clients", &format_args!("{}", clients))
+            .finish()
+    }
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        // Runs once the last clone is gone. Same as stop(), but a poisoned mutex or WinRT error
+        // can't be reported from here, so just make a best effort. A publisher that was already
+        // stopped is left alone.
         let publisher = self
             .publisher
             .lock()
-            .expect("Couldn't lock publisher mutex.");
-        let status = publisher.Status()?;
-        if status == WiFiDirectAdvertisementPublisherStatus::Started {
-            publisher.Stop()?;
-            // self.tx
-            //     .lock()
-            //     .expect("Couldn't lock sender mutex.")
-            //     .send("Hosted network stopped".to_string())
-            //     .expect("Could not send on channel.");
-        } else {
-            self.message_tx
-                .lock()
-                .expect("Couldn't lock sender mutex.")
-                .send("Stop called but WiFiDirectAdvertisementPublisher is not running".to_string())
-                .expect("Could not send on channel.");
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Ok(HostedNetworkStatus::Started) = publisher.status() {
+            let _ = publisher.stop();
+        }
+        self.plumbing
+            .listener
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        self.plumbing.drop_clients();
+        // the workers may hold the last reference and be running this, so they're told to exit
+        // rather than joined
+        self.plumbing.begin_shutdown();
+    }
+}
+
+impl Shared {
+    /// Replaces an aborted publisher with a fresh one using the current credentials, unless the
+    /// network has been stopped or restarted by hand in the meantime.
+    fn restart_aborted(&self) -> Result<()> {
+        let mut publisher = self
+            .publisher
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if self.stopped.load(Ordering::SeqCst)
+            || publisher.status()? != HostedNetworkStatus::Aborted
+        {
+            return Ok(());
         }
+        let credentials = self
+            .credentials
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        *publisher = start(
+            &credentials.ssid,
+            &credentials.passphrase,
+            &self.settings,
+            self.plumbing.clone(),
+            None,
+        )?;
         Ok(())
     }
 }
 
-fn start_listener(tx: Sender<String>) -> Result<()> {
-    let listener = WiFiDirectConnectionListener::new()?;
-    let connection_requested_callback = TypedEventHandler::<
-        WiFiDirectConnectionListener,
-        WiFiDirectConnectionRequestedEventArgs,
-    >::new(move |_sender, args| {
-        tx.send("Connection requested...".to_string())
-            .expect("Couldn't send on tx");
-        let request = args
-            .as_ref()
-            .expect("args == None in connection requested callback")
-            .GetConnectionRequest()?;
-        let device_info = request.DeviceInformation()?;
-        let device_id = device_info.Id()?;
-        let wifi_direct_device = WiFiDirectDevice::FromIdAsync(&device_id)?;
-        let async_operation_completed_callback =
-            AsyncOperationCompletedHandler::<WiFiDirectDevice>::new(|async_operation, status| {
-                if status == AsyncStatus::Completed {
-                    let wfd_device = async_operation
-                        .as_ref()
-                        .expect("No device in WiFiDirectDevice AsyncOperation callback")
-                        .GetResults()?;
-                    let endpoint_pairs = wfd_device.GetConnectionEndpointPairs()?;
-                    let endpoint_pair = endpoint_pairs.GetAt(0)?;
-                    let remote_hostname = endpoint_pair.RemoteHostName()?;
-                    let _display_name = remote_hostname.DisplayName();
-                    let connection_status_changed_callback = TypedEventHandler::<
-                        WiFiDirectDevice,
-                        IInspectable,
-                    >::new(
-                        |sender, _inspectable| {
-                            let status = sender
-                                .as_ref()
-                                .expect("No sender in connection status changed handler")
-                                .ConnectionStatus()?;
-                            // TODO: do we need to do anything here? We don't need to keep track of multiple clients.
-                            // C++ seems to store them in a map but not use them? It does call remove_ConnectionStatusChanged() on the tokens when this disconnected branch hits...
-                            // So I'd like to replicate, but don't know how to reference a map of device IDs and tokens. Arc?
-                            match status {
-                                WiFiDirectConnectionStatus::Disconnected => {
-                                    let _device_id = sender
-                                        .as_ref()
-                                        .expect("No sender in connection status changed handler")
-                                        .DeviceId()?;
-                                }
-                                _ => (),
-                            }
-                            Ok(())
-                        },
-                    );
-                    // In https://github.com/microsoft/Windows-classic-samples/blob/main/Samples/WiFiDirectLegacyAP/cpp/WlanHostedNetworkWinRT.cpp,
-                    // they store this token and the device ID in maps to keep track of connected clients. they don't seem to do anything with them though.
-                    // skipping now as it's not necessary for our purposes.
-                    let _event_registration_token =
-                        wfd_device.ConnectionStatusChanged(&connection_status_changed_callback);
-                }
-                Ok(())
+// Errors in the WinRT handlers, here and in `listener`, are sent to the caller as
+// HostedNetworkEvent::Error rather than handed back to WinRT: the callbacks always return Ok(())
+// and must never panic, as unwinding across the WinRT boundary is unsound.
+
+fn report_error(events: &dyn EventSink, error: HostedNetworkError) {
+    error!("{}", error);
+    events.emit(HostedNetworkEvent::Error { error });
+}
+
+/// Decides from a requesting peer's device ID whether to go ahead with its connection.
+pub(crate) type ConnectionFilter = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// Told whether the publisher came up each time it reaches `Started` or `Aborted`.
+pub(crate) type StartNotifier = Box<dyn FnMut(Result<()>) + Send>;
+
+fn on_publisher_status_changed(
+    change: StatusChange,
+    ssid: &str,
+    accepts_connections: bool,
+    plumbing: &Plumbing,
+    on_start: &mut Option<StartNotifier>,
+) -> Result<()> {
+    let events = &plumbing.events;
+    debug!("Publisher for {} changed status to {}", ssid, change.status);
+    plumbing.trace_status(ssid, change.status);
+    plumbing
+        .status
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .update(change.status);
+    match change.status {
+        // announced by start(), since Windows doesn't reliably report it
+        HostedNetworkStatus::Created => {}
+        HostedNetworkStatus::Stopped => {
+            plumbing.set_started_at(None);
+            events.emit(HostedNetworkEvent::Stopped);
+        }
+        HostedNetworkStatus::Started => {
+            plumbing.set_started_at(Some(Instant::now()));
+            plumbing.set_stop_reason(None);
+            #[cfg(windows)]
+            if accepts_connections {
+                // the listener has to outlive this callback or connection requests will stop
+                // arriving
+                *plumbing
+                    .listener
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner) =
+                    Some(listener::start_listener(plumbing.clone())?);
+            }
+            #[cfg(not(windows))]
+            let _ = accepts_connections;
+            events.emit(HostedNetworkEvent::Started {
+                ssid: change.ssid.as_deref().unwrap_or(ssid).to_string(),
             });
-        wifi_direct_device.SetCompleted(&async_operation_completed_callback)?;
-        Ok(())
-    });
-    listener.ConnectionRequested(&connection_requested_callback)?;
+            // tell caller we started hotspot
+            if let Some(on_start) = on_start {
+                on_start(Ok(()));
+            }
+            if let Some(restart_tx) = &plumbing.restart_tx {
+                let _ = restart_tx.send(RestartSignal::Outcome(Ok(())));
+            }
+        }
+        HostedNetworkStatus::Aborted => {
+            let was_running = plumbing
+                .started_at
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .take()
+                .is_some();
+            // Windows reports a plain abort with WiFiDirectError::Success
+            let error = change.error.unwrap_or(HostedNetworkError::NoCapableAdapter);
+            warn!("Hosted network {} aborted: {}", ssid, error);
+            // tell caller we failed to start hotspot
+            if let Some(on_start) = on_start {
+                on_start(Err(error.clone()));
+            }
+            if let Some(restart_tx) = &plumbing.restart_tx {
+                let _ = restart_tx.send(if was_running {
+                    RestartSignal::Dropped(error.clone())
+                } else {
+                    RestartSignal::Outcome(Err(error.clone()))
+                });
+            }
+            plumbing.set_stop_reason(Some(StopReason::Aborted(error.clone())));
+            events.emit(HostedNetworkEvent::Aborted { error });
+        }
+    }
     Ok(())
 }
 
 fn start(
     ssid: &str,
     password: &str,
-    message_tx: Sender<String>,
-    success_tx: Sender<bool>,
-) -> Result<WiFiDirectAdvertisementPublisher> {
-    let publisher = WiFiDirectAdvertisementPublisher::new()?;
+    settings: &AdvertisementSettings,
+    plumbing: Plumbing,
+    mut on_start: Option<StartNotifier>,
+) -> Result<Box<dyn Publisher>> {
+    let publisher = publisher::create(ssid, password, settings)?;
 
     // add status changed handler
+    let events = plumbing.events.clone();
     let _ssid = ssid.to_string();
-    let publisher_status_changed_callback = TypedEventHandler::<
-        WiFiDirectAdvertisementPublisher,
-        WiFiDirectAdvertisementPublisherStatusChangedEventArgs,
-    >::new(move |_sender, args| {
-        let status = args
-            .as_ref()
-            .expect("args == None in status change callback")
-            .Status()?;
-        match status {
-            WiFiDirectAdvertisementPublisherStatus::Created => message_tx
-                .send("Hosted network created".to_string())
-                .expect("Couldn't send on tx"),
-            WiFiDirectAdvertisementPublisherStatus::Stopped => message_tx
-                .send("Hosted network stopped".to_string())
-                .expect("Couldn't send on tx"),
-            WiFiDirectAdvertisementPublisherStatus::Started => {
-                start_listener(message_tx.clone())?;
-                message_tx
-                    .send(format!("Hosted network {} has started", _ssid))
-                    .expect("Couldn't send on tx");
-                // tell caller we started hotspot
-                success_tx
-                    .send(true)
-                    .expect("Couldn't send hotspot creation success");
-            }
-            WiFiDirectAdvertisementPublisherStatus::Aborted => {
-                let err = match args
-                    .as_ref()
-                    .expect("args == None in status change callback")
-                    .Error()
-                    .expect("Couldn't get error")
-                {
-                    WiFiDirectError::RadioNotAvailable => "Radio not available",
-                    WiFiDirectError::ResourceInUse => "Resource in use",
-                    WiFiDirectError::Success => "No WiFi Direct-capable card or other error",
-                    _ => panic!("got bad WiFiDirectError"),
-                };
-                message_tx
-                    .send(format!("Hosted network aborted: {}", err))
-                    .expect("Couldn't send on tx");
-                // tell caller we failed to start hotspot
-                success_tx
-                    .send(false)
-                    .expect("Couldn't send hotspot creation failure");
-            }
-            _ => panic!("Bad status received in callback."),
-        }
-        Ok(())
-    });
-    publisher.StatusChanged(&publisher_status_changed_callback)?;
-
-    // set advertisement required settings
-    let advertisement = publisher
-        .Advertisement()
-        .expect("Error getting advertisement");
-    advertisement.SetIsAutonomousGroupOwnerEnabled(true)?;
-
-    // set ssid and password
-    let legacy_settings = advertisement.LegacySettings()?;
-    legacy_settings.SetIsEnabled(true)?;
-    let _ssid = HSTRING::from(ssid);
-    legacy_settings.SetSsid(&_ssid)?;
-    let password_credential = PasswordCredential::new()?;
-    password_credential.SetPassword(&HSTRING::from(password))?;
-    legacy_settings.SetPassphrase(&password_credential)?;
-
-    // Start the advertisement, which will create an access point that other peers can connect to
-    publisher.Start()?;
+    let accepts_connections = publisher.accepts_connections();
+    publisher.subscribe(Box::new(move |change| {
+        plumbing.in_span(|| {
+            let result = change.and_then(|change| {
+                on_publisher_status_changed(
+                    change,
+                    &_ssid,
+                    accepts_connections,
+                    &plumbing,
+                    &mut on_start,
+                )
+            });
+            if let Err(error) = result {
+                report_error(&*plumbing.events, error);
+            }
+        });
+    }))?;
 
+    // once per start, and ahead of the Started or Aborted the publisher reports
+    events.emit(HostedNetworkEvent::Created);
+    publisher.start()?;
     Ok(publisher)
 }
 
 #[cfg(test)]
+// most of these start a network, which takes Windows or the mock feature
+#[cfg_attr(not(any(windows, feature = "mock")), allow(unused_imports))]
 mod tests {
-    use crate::WlanHostedNetworkHelper;
+    use crate::{
+        HostedNetworkError, HostedNetworkEvent, HostedNetworkManager, HostedNetworkStatus,
+        WlanHostedNetworkBuilder, WlanHostedNetworkHelper,
+    };
     use std::sync::mpsc;
     use std::thread::spawn;
 
-    // run with `cargo test -- --nocapture` to see output
+    // checked when the tests compile, so a field that isn't Send or Sync fails the build
+    #[test]
+    fn handles_are_send_and_sync() {
+        fn assert_send<T: Send>() {}
+        fn assert_sync<T: Sync>() {}
+        assert_send::<WlanHostedNetworkHelper>();
+        assert_sync::<WlanHostedNetworkHelper>();
+        assert_send::<WlanHostedNetworkBuilder>();
+        assert_sync::<WlanHostedNetworkBuilder>();
+        assert_send::<HostedNetworkManager>();
+        assert_sync::<HostedNetworkManager>();
+        assert_send::<HostedNetworkError>();
+        assert_sync::<HostedNetworkError>();
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn futures_are_send() {
+        fn assert_send<T: Send>(_: &T) {}
+        let (message_tx, _message_rx) = mpsc::channel();
+        let start = WlanHostedNetworkHelper::start_async(
+            "WiFiDirectTestNetwork",
+            "TestingThisLibrary",
+            message_tx,
+        );
+        assert_send(&start);
+    }
+
+    // run with `cargo test -- --nocapture` to see output. Needs a real adapter, so it's left out of
+    // mock builds.
+    #[cfg(all(windows, not(feature = "mock")))]
     #[test]
     fn run_hosted_network() {
         // Make channels to receive messages from Windows Runtime
-        let (message_tx, message_rx) = mpsc::channel::<String>();
+        let (message_tx, message_rx) = mpsc::channel::<HostedNetworkEvent>();
         let (success_tx, success_rx) = mpsc::channel::<bool>();
         let wlan_hosted_network_helper = WlanHostedNetworkHelper::new(
             "WiFiDirectTestNetwork",
@@ -245,4 +1374,122 @@ mod tests {
         // Stop it when done
         wlan_hosted_network_helper.stop().expect("Error in stop()");
     }
+
+    #[cfg(any(windows, feature = "mock"))]
+    #[test]
+    fn clones_share_one_network() {
+        let (message_tx, message_rx) = mpsc::channel::<HostedNetworkEvent>();
+        let (success_tx, success_rx) = mpsc::channel::<bool>();
+        let wlan_hosted_network_helper = WlanHostedNetworkHelper::new(
+            "WiFiDirectCloneTestNetwork",
+            "TestingThisLibrary",
+            message_tx,
+            success_tx,
+        )
+        .unwrap();
+        assert!(success_rx.recv().unwrap(), "Failed to start hotspot");
+
+        // dropping a clone on another thread leaves the network up
+        let clone = wlan_hosted_network_helper.clone();
+        spawn(move || assert_eq!(clone.status().unwrap(), HostedNetworkStatus::Started))
+            .join()
+            .unwrap();
+        assert_eq!(
+            wlan_hosted_network_helper.status().unwrap(),
+            HostedNetworkStatus::Started
+        );
+
+        // stopping any clone stops it for all of them
+        let clone = wlan_hosted_network_helper.clone();
+        spawn(move || clone.stop().expect("Error in stop()"))
+            .join()
+            .unwrap();
+        loop {
+            match message_rx.recv_timeout(std::time::Duration::from_secs(10)) {
+                Ok(HostedNetworkEvent::Stopped) => break,
+                Ok(_) => {}
+                Err(e) => panic!("Network didn't stop: {}", e),
+            }
+        }
+        assert_eq!(
+            wlan_hosted_network_helper.status().unwrap(),
+            HostedNetworkStatus::Stopped
+        );
+    }
+
+    #[cfg(all(feature = "tokio", any(windows, feature = "mock")))]
+    #[tokio::test]
+    async fn stream_reports_started() {
+        use tokio_stream::StreamExt;
+
+        let (wlan_hosted_network_helper, mut events) = WlanHostedNetworkHelper::new_with_stream(
+            "WiFiDirectStreamTestNetwork",
+            "TestingThisLibrary",
+        )
+        .unwrap();
+        loop {
+            match events.next().await {
+                Some(HostedNetworkEvent::Started { ssid }) => {
+                    assert_eq!(ssid, "WiFiDirectStreamTestNetwork");
+                    break;
+                }
+                Some(HostedNetworkEvent::Aborted { error }) => panic!("Failed to start: {}", error),
+                Some(_) => {}
+                None => panic!("Event stream ended before the network started"),
+            }
+        }
+        wlan_hosted_network_helper.stop().expect("Error in stop()");
+    }
+
+    #[cfg(any(windows, feature = "mock"))]
+    #[test]
+    fn dropped_receiver_does_not_panic() {
+        let (message_tx, message_rx) = mpsc::channel::<HostedNetworkEvent>();
+        let (success_tx, success_rx) = mpsc::channel::<bool>();
+        drop(message_rx);
+        let wlan_hosted_network_helper = WlanHostedNetworkHelper::new(
+            "WiFiDirectDroppedReceiverNetwork",
+            "TestingThisLibrary",
+            message_tx,
+            success_tx,
+        )
+        .unwrap();
+        // Started and Stopped are both sent from status changed callbacks with nobody listening
+        assert!(success_rx.recv().unwrap(), "Failed to start hotspot");
+        wlan_hosted_network_helper.stop().expect("Error in stop()");
+        std::thread::sleep(std::time::Duration::from_secs(2));
+        // NotRunning is sent from the caller's thread instead
+        wlan_hosted_network_helper.stop().expect("Error in stop()");
+    }
+
+    #[cfg(any(windows, feature = "mock"))]
+    // needs a peer to join "WiFiDirectListenerTestNetwork" by hand once the network is up
+    #[test]
+    #[ignore]
+    fn listener_outlives_start() {
+        let (message_tx, message_rx) = mpsc::channel::<HostedNetworkEvent>();
+        let (success_tx, success_rx) = mpsc::channel::<bool>();
+        let wlan_hosted_network_helper = WlanHostedNetworkHelper::new(
+            "WiFiDirectListenerTestNetwork",
+            "TestingThisLibrary",
+            message_tx,
+            success_tx,
+        )
+        .unwrap();
+        assert!(success_rx.recv().unwrap(), "Failed to start hotspot");
+
+        // Give the listener plenty of time to be dropped if nothing is holding on to it
+        std::thread::sleep(std::time::Duration::from_secs(5));
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(120);
+        loop {
+            let timeout = deadline.saturating_duration_since(std::time::Instant::now());
+            match message_rx.recv_timeout(timeout) {
+                Ok(HostedNetworkEvent::ConnectionRequested { .. }) => break,
+                Ok(msg) => println!("{}", msg),
+                Err(e) => panic!("No connection request received: {}", e),
+            }
+        }
+
+        wlan_hosted_network_helper.stop().expect("Error in stop()");
+    }
 }
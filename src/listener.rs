@@ -0,0 +1,224 @@
+//! Taking connections on Windows: the `WiFiDirectConnectionListener` that receives peers'
+//! requests, resolving the accepted ones to a `WiFiDirectDevice`, and watching each client until
+//! it disconnects. What happens to a peer in between is up to `connection`.
+
+use crate::client::ClientDevice;
+use crate::connection::{admit, client_gone, register_client};
+use crate::platform::{
+    self, AsyncOperationCompletedHandler, AsyncStatus, EventRegistrationToken, IAsyncOperation,
+    IInspectable, TypedEventHandler, WiFiDirectConnectionListener, WiFiDirectConnectionParameters,
+    WiFiDirectConnectionRequestedEventArgs, WiFiDirectConnectionStatus, WiFiDirectDevice, HRESULT,
+};
+use crate::{report_error, DeviceInfo, HostedNetworkError, HostedNetworkEvent, Plumbing, Result};
+
+/// Keeps the `WiFiDirectConnectionListener` and its handler registered for as long as it is held.
+pub(crate) struct ConnectionListener {
+    listener: WiFiDirectConnectionListener,
+    token: EventRegistrationToken,
+}
+
+impl Drop for ConnectionListener {
+    fn drop(&mut self) {
+        let _ = self.listener.RemoveConnectionRequested(self.token);
+    }
+}
+
+pub(crate) fn start_listener(plumbing: Plumbing) -> Result<ConnectionListener> {
+    let listener = WiFiDirectConnectionListener::new()?;
+    let connection_requested_callback = TypedEventHandler::<
+        WiFiDirectConnectionListener,
+        WiFiDirectConnectionRequestedEventArgs,
+    >::new(move |_sender, args| {
+        plumbing.in_span(|| {
+            if let Err(error) = on_connection_requested(args, &plumbing) {
+                report_error(&*plumbing.events, error);
+            }
+        });
+        Ok(())
+    });
+    let token = listener.ConnectionRequested(&connection_requested_callback)?;
+    Ok(ConnectionListener { listener, token })
+}
+
+// Errors in the handlers below are sent to the caller as HostedNetworkEvent::Error rather than
+// handed back to WinRT, the same as the publisher's status handler.
+
+fn on_connection_requested(
+    args: &Option<WiFiDirectConnectionRequestedEventArgs>,
+    plumbing: &Plumbing,
+) -> Result<()> {
+    let request = args
+        .as_ref()
+        .ok_or(HostedNetworkError::missing_event_args(
+            "connection requested callback",
+        ))?
+        .GetConnectionRequest()?;
+    let device_info = request.DeviceInformation()?;
+    let device_id = device_info.Id()?;
+    debug!("Connection requested by {}", device_id);
+    // some peers don't advertise a name, so DeviceInfo shows them by ID rather than not at all
+    let device = DeviceInfo::read(&device_info);
+    // dropping the request without resolving the device declines it
+    let decline = || Ok(request.Close()?);
+    if !admit(&device_id.to_string(), &device, decline, plumbing)? {
+        return Ok(());
+    }
+    let wifi_direct_device = match plumbing.group_owner_intent {
+        Some(intent) => {
+            let parameters = WiFiDirectConnectionParameters::new()?;
+            parameters.SetGroupOwnerIntent(i16::from(intent))?;
+            WiFiDirectDevice::FromIdAsync2(&device_id, &parameters)?
+        }
+        None => WiFiDirectDevice::FromIdAsync(&device_id)?,
+    };
+    let device_id = device_id.to_string();
+    let plumbing = plumbing.clone();
+    let async_operation_completed_callback =
+        AsyncOperationCompletedHandler::<WiFiDirectDevice>::new(move |async_operation, status| {
+            plumbing.in_span(|| {
+                if let Err(error) =
+                    on_device_resolved(&device_id, &device, async_operation, status, &plumbing)
+                {
+                    report_error(&*plumbing.events, error);
+                }
+            });
+            Ok(())
+        });
+    wifi_direct_device.SetCompleted(&async_operation_completed_callback)?;
+    Ok(())
+}
+
+fn on_device_resolved(
+    requested_id: &str,
+    device: &DeviceInfo,
+    async_operation: &Option<IAsyncOperation<WiFiDirectDevice>>,
+    status: AsyncStatus,
+    plumbing: &Plumbing,
+) -> Result<()> {
+    if status == AsyncStatus::Completed {
+        let wfd_device = async_operation
+            .as_ref()
+            .ok_or(HostedNetworkError::missing_event_args(
+                "WiFiDirectDevice AsyncOperation callback",
+            ))?
+            .GetResults()?;
+        debug!("Resolved WiFiDirectDevice {}", requested_id);
+        let endpoint_pairs = wfd_device.GetConnectionEndpointPairs()?;
+        // the pairs can be empty for a moment after connecting, ClientReady follows once they
+        // aren't
+        let remote_host = if endpoint_pairs.Size()? == 0 {
+            None
+        } else {
+            Some(
+                endpoint_pairs
+                    .GetAt(0)?
+                    .RemoteHostName()?
+                    .DisplayName()?
+                    .to_string(),
+            )
+        };
+        let device_id = wfd_device.DeviceId()?.to_string();
+        register_client(
+            device_id,
+            device,
+            ClientDevice::WinRt(wfd_device),
+            remote_host,
+            plumbing,
+        )?;
+    } else if status == AsyncStatus::Error || status == AsyncStatus::Canceled {
+        let error_code = async_operation
+            .as_ref()
+            .ok_or(HostedNetworkError::missing_event_args(
+                "WiFiDirectDevice AsyncOperation callback",
+            ))?
+            .ErrorCode()?;
+        let error = resolution_error(status, error_code);
+        warn!(
+            "Resolving WiFiDirectDevice {} failed: {}",
+            requested_id, error
+        );
+        plumbing
+            .events
+            .emit(HostedNetworkEvent::ClientConnectFailed {
+                device_id: requested_id.to_string(),
+                error,
+            });
+    }
+    // AsyncStatus::Started can't get here, the completed handler only runs once it's over
+    Ok(())
+}
+
+/// Registers for the client's connection status changes, so it's reported once it goes away.
+/// Mock devices only go away through `mock_disconnect()`, so they need no handler.
+pub(crate) fn watch_connection(
+    device: &ClientDevice,
+    plumbing: &Plumbing,
+) -> Result<EventRegistrationToken> {
+    match device {
+        ClientDevice::WinRt(device) => {
+            let handler_plumbing = plumbing.clone();
+            let connection_status_changed_callback =
+                TypedEventHandler::<WiFiDirectDevice, IInspectable>::new(
+                    move |sender, _inspectable| {
+                        handler_plumbing.in_span(|| {
+                            if let Err(error) =
+                                on_connection_status_changed(sender, &handler_plumbing)
+                            {
+                                report_error(&*handler_plumbing.events, error);
+                            }
+                        });
+                        Ok(())
+                    },
+                );
+            Ok(device.ConnectionStatusChanged(&connection_status_changed_callback)?)
+        }
+        #[cfg(feature = "mock")]
+        ClientDevice::Mock(_) => Ok(EventRegistrationToken::default()),
+    }
+}
+
+/// Why `FromIdAsync` finished without a device.
+fn resolution_error(status: AsyncStatus, error_code: HRESULT) -> HostedNetworkError {
+    if status == AsyncStatus::Canceled {
+        HostedNetworkError::Canceled
+    } else {
+        platform::Error::from(error_code).into()
+    }
+}
+
+fn on_connection_status_changed(
+    sender: &Option<WiFiDirectDevice>,
+    plumbing: &Plumbing,
+) -> Result<()> {
+    let device = sender
+        .as_ref()
+        .ok_or(HostedNetworkError::missing_event_args(
+            "connection status changed handler",
+        ))?;
+    let status = device.ConnectionStatus()?;
+    if status == WiFiDirectConnectionStatus::Disconnected {
+        client_gone(device.DeviceId()?.to_string(), plumbing)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::resolution_error;
+    use crate::platform::{AsyncStatus, HRESULT};
+    use crate::HostedNetworkError;
+
+    #[test]
+    fn failed_device_resolution() {
+        // ERROR_GEN_FAILURE, which flaky Wi-Fi drivers like to return
+        let code = HRESULT(0x8007001Fu32 as i32);
+        match resolution_error(AsyncStatus::Error, code) {
+            HostedNetworkError::Windows(error) => assert_eq!(error.code, code.0),
+            error => panic!("unexpected error {:?}", error),
+        }
+        assert_eq!(
+            resolution_error(AsyncStatus::Canceled, HRESULT(0)),
+            HostedNetworkError::Canceled
+        );
+    }
+}
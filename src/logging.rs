@@ -0,0 +1,29 @@
+// Thin wrappers over the `log` macros so diagnostics compile away entirely without the `log`
+// feature. The arguments still go through `format_args!` so they count as used either way.
+
+macro_rules! debug {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "log")]
+        log::debug!($($arg)*);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)*);
+    }};
+}
+
+macro_rules! warn {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "log")]
+        log::warn!($($arg)*);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)*);
+    }};
+}
+
+macro_rules! error {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "log")]
+        log::error!($($arg)*);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)*);
+    }};
+}
@@ -0,0 +1,101 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::sync;
+use crate::{
+    HostedNetworkError, HostedNetworkStatus, Result, WlanHostedNetworkBuilder,
+    WlanHostedNetworkHelper,
+};
+
+/// Keeps track of hosted networks by name.
+///
+/// Windows runs at most one Wi-Fi Direct legacy access point at a time, so only one network can
+/// be active here too: `start()` returns `HostedNetworkError::OnlyOneNetworkSupported` while
+/// another is starting or running. A network that has stopped or aborted doesn't count, and is
+/// replaced by the next one started.
+#[derive(Debug, Default)]
+pub struct HostedNetworkManager {
+    networks: Mutex<BTreeMap<String, WlanHostedNetworkHelper>>,
+}
+
+impl HostedNetworkManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds and starts a network from `config` under `name`, returning a handle to it.
+    pub fn start(
+        &self,
+        name: &str,
+        config: &WlanHostedNetworkBuilder,
+    ) -> Result<WlanHostedNetworkHelper> {
+        let mut networks = sync::lock(&self.networks, "networks");
+        // forget networks that are already down, which drops them
+        networks.retain(|_, network| is_active(network));
+        if !networks.is_empty() {
+            return Err(HostedNetworkError::OnlyOneNetworkSupported);
+        }
+        let network = config.build()?;
+        networks.insert(name.to_string(), network.clone());
+        Ok(network)
+    }
+
+    /// Stops the network called `name` and forgets it.
+    pub fn stop(&self, name: &str) -> Result<()> {
+        let network = sync::lock(&self.networks, "networks")
+            .remove(name)
+            .ok_or_else(|| HostedNetworkError::UnknownNetwork(name.to_string()))?;
+        network.stop()
+    }
+
+    /// Stops and forgets every network, returning the first error after trying them all.
+    pub fn stop_all(&self) -> Result<()> {
+        let networks = std::mem::take(&mut *sync::lock(&self.networks, "networks"));
+        let mut result = Ok(());
+        for network in networks.into_values() {
+            if let Err(e) = network.stop() {
+                result = result.and(Err(e));
+            }
+        }
+        result
+    }
+
+    /// Returns a handle to the network called `name`, if there is one.
+    pub fn get(&self, name: &str) -> Option<WlanHostedNetworkHelper> {
+        sync::lock(&self.networks, "networks").get(name).cloned()
+    }
+
+    /// Returns the names of the networks being tracked, in sorted order.
+    pub fn list(&self) -> Vec<String> {
+        sync::lock(&self.networks, "networks")
+            .keys()
+            .cloned()
+            .collect()
+    }
+}
+
+fn is_active(network: &WlanHostedNetworkHelper) -> bool {
+    // a network whose status can't be read is treated as still holding the radio
+    !matches!(
+        network.status(),
+        Ok(HostedNetworkStatus::Stopped) | Ok(HostedNetworkStatus::Aborted)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HostedNetworkManager;
+    use crate::HostedNetworkError;
+
+    #[test]
+    fn unknown_networks_are_reported() {
+        let manager = HostedNetworkManager::new();
+        assert!(manager.list().is_empty());
+        assert!(manager.get("onboarding").is_none());
+        assert_eq!(
+            manager.stop("onboarding"),
+            Err(HostedNetworkError::UnknownNetwork("onboarding".to_string()))
+        );
+        assert_eq!(manager.stop_all(), Ok(()));
+    }
+}
@@ -0,0 +1,1233 @@
+//! A stand-in publisher for the `mock` feature. It never touches the radio: starting reports
+//! `Started` straight away, on the calling thread, and tests make it abort with `abort()`.
+//!
+//! Peers can be faked too, with the helper's `mock_connection_request()` and `mock_disconnect()`.
+//! They go through the same admission checks, client registry and events as real ones, with only
+//! the `WiFiDirectDevice` replaced by a `MockDevice`.
+
+// the controls and recorded settings are only read by tests
+#![cfg_attr(not(test), allow(dead_code))]
+
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::builder::AdvertisementSettings;
+use crate::client::ClientDevice;
+use crate::connection::{admit, client_gone, register_client};
+use crate::publisher::{Publisher, StatusChange, StatusHandler};
+use crate::{
+    DeviceInfo, EndpointPair, HostedNetworkError, HostedNetworkStatus, Result,
+    WlanHostedNetworkHelper,
+};
+
+/// Clones share the same fake publisher, so a test can keep a handle to the one a helper uses.
+#[derive(Clone)]
+pub(crate) struct MockPublisher {
+    state: Arc<Mutex<MockState>>,
+    // kept apart from the state so the handler can be run without holding the state's lock
+    handler: Arc<Mutex<Option<StatusHandler>>>,
+}
+
+/// Stands in for the `DIRECT-xy-<PC name>` SSID Windows generates when none is given.
+pub(crate) const MOCK_GENERATED_SSID: &str = "DIRECT-mk-MockNetwork";
+
+struct MockState {
+    status: HostedNetworkStatus,
+    ssid: String,
+    passphrase: String,
+    settings: AdvertisementSettings,
+}
+
+impl MockPublisher {
+    pub(crate) fn new(ssid: &str, password: &str, settings: &AdvertisementSettings) -> Self {
+        MockPublisher {
+            state: Arc::new(Mutex::new(MockState {
+                status: HostedNetworkStatus::Created,
+                ssid: ssid.to_string(),
+                passphrase: password.to_string(),
+                settings: settings.clone(),
+            })),
+            handler: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Aborts the publisher with `error`, as Windows does when the radio goes away.
+    pub(crate) fn abort(&self, error: HostedNetworkError) {
+        self.set_status(HostedNetworkStatus::Aborted, Some(error));
+    }
+
+    pub(crate) fn ssid(&self) -> String {
+        self.lock().ssid.clone()
+    }
+
+    pub(crate) fn passphrase(&self) -> String {
+        self.lock().passphrase.clone()
+    }
+
+    /// The advertisement settings the publisher was created with.
+    pub(crate) fn settings(&self) -> AdvertisementSettings {
+        self.lock().settings.clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn set_status(&self, status: HostedNetworkStatus, error: Option<HostedNetworkError>) {
+        let ssid = {
+            let mut state = self.lock();
+            state.status = status;
+            if status == HostedNetworkStatus::Started && state.ssid.is_empty() {
+                // what Windows would make up, give or take the random letters
+                state.ssid = MOCK_GENERATED_SSID.to_string();
+            }
+            Some(state.ssid.clone()).filter(|_| status == HostedNetworkStatus::Started)
+        };
+        let mut handler = self.handler.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(handler) = handler.as_mut() {
+            handler(Ok(StatusChange {
+                status,
+                error,
+                ssid,
+            }));
+        }
+    }
+}
+
+impl Publisher for MockPublisher {
+    fn start(&self) -> Result<()> {
+        self.set_status(HostedNetworkStatus::Started, None);
+        Ok(())
+    }
+
+    fn stop(&self) -> Result<()> {
+        if self.lock().status == HostedNetworkStatus::Started {
+            self.set_status(HostedNetworkStatus::Stopped, None);
+        }
+        Ok(())
+    }
+
+    fn status(&self) -> Result<HostedNetworkStatus> {
+        Ok(self.lock().status)
+    }
+
+    fn ssid(&self) -> Result<String> {
+        Ok(self.lock().ssid.clone())
+    }
+
+    fn subscribe(&self, handler: StatusHandler) -> Result<()> {
+        *self.handler.lock().unwrap_or_else(PoisonError::into_inner) = Some(handler);
+        Ok(())
+    }
+
+    fn accepts_connections(&self) -> bool {
+        // peers come from mock_connection_request() instead
+        false
+    }
+
+    fn unsubscribe(&self) -> Result<()> {
+        self.handler
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        Ok(())
+    }
+
+    #[cfg(test)]
+    fn as_mock(&self) -> Option<&MockPublisher> {
+        Some(self)
+    }
+}
+
+/// A peer made up by `mock_connection_request()`. Clones share the endpoint pairs, so
+/// `mock_endpoints()` reaches the copy in the client registry.
+#[derive(Clone, Default)]
+pub(crate) struct MockDevice {
+    endpoint_pairs: Arc<Mutex<Vec<EndpointPair>>>,
+}
+
+impl MockDevice {
+    pub(crate) fn endpoint_pairs(&self) -> Vec<EndpointPair> {
+        self.endpoint_pairs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+/// Fake peers, for testing how an app handles them without a second device. Only with the `mock`
+/// feature.
+impl WlanHostedNetworkHelper {
+    /// Acts as if the peer `device_id`, called `name`, asked to connect. It's let in or turned
+    /// away exactly as a real peer would be, by `pause_advertising()`, `max_clients`, the access
+    /// list, the `connection_filter` and `on_connection_request`, and the same events are sent.
+    /// An accepted peer is connected straight away, with no addresses until `mock_endpoints()`
+    /// gives it some, so `ClientConnected` has `endpoints_pending` set and `ClientReady` follows
+    /// once it has one.
+    pub fn mock_connection_request(&self, device_id: &str, name: &str) -> Result<()> {
+        let plumbing = &self.inner.plumbing;
+        plumbing.in_span(|| {
+            let device = DeviceInfo {
+                id: device_id.to_string(),
+                // as DeviceInfo::read() does for peers without a name
+                name: if name.is_empty() { device_id } else { name }.to_string(),
+                kind: "AssociationEndpoint".to_string(),
+                is_paired: false,
+                can_pair: true,
+                properties: Default::default(),
+            };
+            // there's no request to close
+            if !admit(device_id, &device, || Ok(()), plumbing)? {
+                return Ok(());
+            }
+            register_client(
+                device_id.to_string(),
+                &device,
+                ClientDevice::Mock(MockDevice::default()),
+                None,
+                plumbing,
+            )
+        })
+    }
+
+    /// Gives a peer from `mock_connection_request()` these endpoint pairs, replacing any it had,
+    /// as when Windows assigns it an address or it renews its DHCP lease.
+    pub fn mock_endpoints(&self, device_id: &str, pairs: &[EndpointPair]) -> Result<()> {
+        let clients = self.inner.plumbing.clients.lock();
+        match clients.get(device_id).map(|client| &client.device) {
+            Some(ClientDevice::Mock(device)) => {
+                *device
+                    .endpoint_pairs
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner) = pairs.to_vec();
+                Ok(())
+            }
+            _ => Err(HostedNetworkError::UnknownClient(device_id.to_string())),
+        }
+    }
+
+    /// Acts as if a peer from `mock_connection_request()` went away on its own, sending
+    /// `ClientDisconnected` and starting its `reconnect_window`, where `disconnect_client()` is
+    /// this PC dropping it. A real peer's connection would be forgotten but left open.
+    pub fn mock_disconnect(&self, device_id: &str) -> Result<()> {
+        let plumbing = &self.inner.plumbing;
+        if plumbing.in_span(|| client_gone(device_id.to_string(), plumbing))? {
+            Ok(())
+        } else {
+            Err(HostedNetworkError::UnknownClient(device_id.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MockPublisher, MOCK_GENERATED_SSID};
+    use crate::builder::AdvertisementSettings;
+    use crate::publisher::Publisher;
+    #[cfg(feature = "tokio")]
+    use crate::StopReason;
+    use crate::{
+        Credentials, DeviceInfo, EndpointPair, HostedNetworkError, HostedNetworkEvent,
+        HostedNetworkStatus, OverflowPolicy, RetryPolicy, WlanHostedNetworkBuilder,
+        WlanHostedNetworkHelper, WpsMethod,
+    };
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    const PEER: &str = "WiFiDirect#02:8b:21:42:ac:36";
+
+    fn pair(remote: &str) -> EndpointPair {
+        EndpointPair {
+            local: "192.168.137.1".parse().unwrap(),
+            remote: remote.parse().unwrap(),
+        }
+    }
+
+    fn mock_network(message_tx: mpsc::Sender<HostedNetworkEvent>) -> WlanHostedNetworkHelper {
+        WlanHostedNetworkBuilder::new()
+            .ssid("WiFiDirectMockNetwork")
+            .passphrase("TestingThisLibrary")
+            .event_sender(message_tx)
+            .build()
+            .unwrap()
+    }
+
+    fn unknown(device_id: &str) -> HostedNetworkError {
+        HostedNetworkError::UnknownClient(device_id.to_string())
+    }
+
+    #[test]
+    fn start_and_stop_are_reported() {
+        let (message_tx, message_rx) = mpsc::channel();
+        let (success_tx, success_rx) = mpsc::channel();
+        let helper = WlanHostedNetworkBuilder::new()
+            .ssid("WiFiDirectMockNetwork")
+            .passphrase("TestingThisLibrary")
+            .event_sender(message_tx)
+            .success_sender(success_tx)
+            .build()
+            .unwrap();
+        assert!(success_rx.recv().unwrap());
+        assert_eq!(helper.status(), Ok(HostedNetworkStatus::Started));
+        let publisher = helper.mock_publisher();
+        assert_eq!(publisher.ssid(), "WiFiDirectMockNetwork");
+        assert_eq!(publisher.passphrase(), "TestingThisLibrary");
+        assert!(publisher.settings().autonomous_group_owner);
+        assert_eq!(publisher.settings().username, None);
+        assert!(helper.uptime().is_some());
+        assert!(helper.is_running());
+        helper.stop().unwrap();
+        assert_eq!(helper.status(), Ok(HostedNetworkStatus::Stopped));
+        assert_eq!(helper.uptime(), None);
+        assert!(!helper.is_running());
+        let events: Vec<_> = message_rx.try_iter().collect();
+        assert_eq!(
+            events,
+            vec![
+                HostedNetworkEvent::Created,
+                HostedNetworkEvent::Started {
+                    ssid: "WiFiDirectMockNetwork".to_string()
+                },
+                HostedNetworkEvent::Stopped,
+            ]
+        );
+    }
+
+    #[test]
+    fn created_comes_once_before_every_start() {
+        let (message_tx, message_rx) = mpsc::channel();
+        let helper = mock_network(message_tx);
+        helper
+            .restart("WiFiDirectMockNetwork2", "TestingThisLibrary")
+            .unwrap();
+        let events: Vec<_> = message_rx.try_iter().collect();
+        assert_eq!(
+            events,
+            vec![
+                HostedNetworkEvent::Created,
+                HostedNetworkEvent::Started {
+                    ssid: "WiFiDirectMockNetwork".to_string()
+                },
+                HostedNetworkEvent::Stopped,
+                HostedNetworkEvent::Created,
+                HostedNetworkEvent::Started {
+                    ssid: "WiFiDirectMockNetwork2".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn repeated_restarts_leave_no_clients() {
+        let (message_tx, _message_rx) = mpsc::channel();
+        let helper = mock_network(message_tx);
+        for _ in 0..5 {
+            helper.stop().unwrap();
+            assert_eq!(helper.client_count(), 0);
+            assert!(!helper.is_running());
+            assert!(!helper.is_client_connected("WiFiDirect#02:8b:21:42:ac:36"));
+            helper
+                .restart("WiFiDirectMockNetwork", "TestingThisLibrary")
+                .unwrap();
+            assert!(helper.is_running());
+        }
+        helper.stop().unwrap();
+        assert_eq!(helper.client_count(), 0);
+    }
+
+    #[test]
+    fn mock_network_starts_without_a_listener() {
+        let (message_tx, _message_rx) = mpsc::channel();
+        let helper = mock_network(message_tx);
+        assert!(helper.is_running());
+        assert!(helper.inner.plumbing.listener.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn username_is_kept_across_restarts() {
+        let (message_tx, _message_rx) = mpsc::channel();
+        let helper = WlanHostedNetworkBuilder::new()
+            .ssid("WiFiDirectMockNetwork")
+            .passphrase("TestingThisLibrary")
+            .username("mock-user")
+            .event_sender(message_tx)
+            .build()
+            .unwrap();
+        helper
+            .restart("WiFiDirectMockNetwork2", "TestingThisLibrary")
+            .unwrap();
+        let settings = helper.mock_publisher().settings();
+        assert_eq!(settings.username.as_deref(), Some("mock-user"));
+    }
+
+    #[test]
+    fn autonomous_group_owner_is_applied() {
+        for enabled in [true, false] {
+            let (message_tx, _message_rx) = mpsc::channel();
+            let helper = WlanHostedNetworkBuilder::new()
+                .ssid("WiFiDirectMockNetwork")
+                .passphrase("TestingThisLibrary")
+                .autonomous_group_owner(enabled)
+                .event_sender(message_tx)
+                .build()
+                .unwrap();
+            let settings = helper.mock_publisher().settings();
+            assert_eq!(settings.autonomous_group_owner, enabled);
+            // and it survives a restart
+            helper
+                .restart("WiFiDirectMockNetwork2", "TestingThisLibrary")
+                .unwrap();
+            let settings = helper.mock_publisher().settings();
+            assert_eq!(settings.autonomous_group_owner, enabled);
+        }
+    }
+
+    #[test]
+    fn group_owner_intent_needs_negotiation() {
+        let (message_tx, _message_rx) = mpsc::channel();
+        let mut builder = WlanHostedNetworkBuilder::new();
+        builder
+            .ssid("WiFiDirectMockNetwork")
+            .passphrase("TestingThisLibrary")
+            .group_owner_intent(7)
+            .event_sender(message_tx);
+        // still an autonomous group owner, so there's nothing to negotiate
+        assert!(matches!(
+            builder.build(),
+            Err(HostedNetworkError::InvalidGroupOwnerIntent(_))
+        ));
+        builder.autonomous_group_owner(false);
+        assert!(builder.build().unwrap().is_running());
+        builder.group_owner_intent(16);
+        assert!(matches!(
+            builder.build(),
+            Err(HostedNetworkError::InvalidGroupOwnerIntent(_))
+        ));
+    }
+
+    #[test]
+    fn max_clients_defaults_to_none() {
+        let (message_tx, _message_rx) = mpsc::channel();
+        assert_eq!(mock_network(message_tx).max_clients(), None);
+    }
+
+    #[test]
+    fn max_clients_is_reported() {
+        let (message_tx, _message_rx) = mpsc::channel();
+        let helper = WlanHostedNetworkBuilder::new()
+            .ssid("WiFiDirectMockNetwork")
+            .passphrase("TestingThisLibrary")
+            .max_clients(4)
+            .event_sender(message_tx)
+            .build()
+            .unwrap();
+        assert_eq!(helper.max_clients(), Some(4));
+    }
+
+    #[test]
+    fn information_elements_are_applied() {
+        let (message_tx, _message_rx) = mpsc::channel();
+        let mut builder = WlanHostedNetworkBuilder::new();
+        builder
+            .ssid("WiFiDirectMockNetwork")
+            .passphrase("TestingThisLibrary")
+            .add_information_element([0x00, 0x50, 0xf2], 4, b"file-transfer")
+            .event_sender(message_tx);
+        let helper = builder.build().unwrap();
+        let elements = helper.mock_publisher().settings().information_elements;
+        assert_eq!(elements.len(), 1);
+        assert_eq!(elements[0].data, b"file-transfer");
+        drop(helper);
+        // one byte more than fits in an element
+        builder.add_information_element([0x00, 0x50, 0xf2], 5, &[0; 252]);
+        assert!(matches!(
+            builder.build(),
+            Err(HostedNetworkError::InvalidInformationElement(_))
+        ));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn stopped_reports_why() {
+        let (message_tx, _message_rx) = mpsc::channel();
+        let helper = mock_network(message_tx);
+        let stopped = helper.stopped();
+        helper.stop().unwrap();
+        assert_eq!(stopped.await, StopReason::Stopped);
+        helper
+            .restart("WiFiDirectMockNetwork", "TestingThisLibrary")
+            .unwrap();
+        let stopped = helper.stopped();
+        helper
+            .mock_publisher()
+            .abort(HostedNetworkError::ResourceInUse);
+        assert_eq!(
+            stopped.await,
+            StopReason::Aborted(HostedNetworkError::ResourceInUse)
+        );
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn shutdown_resolves_stopped() {
+        let (message_tx, _message_rx) = mpsc::channel();
+        let helper = mock_network(message_tx);
+        let stopped = helper.stopped();
+        helper.shutdown().unwrap();
+        assert_eq!(stopped.await, StopReason::Stopped);
+    }
+
+    #[test]
+    fn join_workers_waits_for_every_worker() {
+        let (message_tx, message_rx) = mpsc::channel();
+        let helper = WlanHostedNetworkBuilder::new()
+            .ssid("WiFiDirectMockNetwork")
+            .passphrase("TestingThisLibrary")
+            .auto_restart(RetryPolicy::new(3, Duration::from_millis(10)))
+            .idle_timeout(Duration::from_secs(60))
+            .event_buffer(8, OverflowPolicy::Block)
+            .event_sender(message_tx)
+            .build()
+            .unwrap();
+        helper.join_workers().unwrap();
+        assert!(!helper.is_running());
+        // the buffer's thread delivered everything before exiting
+        let events: Vec<_> = message_rx.try_iter().collect();
+        assert_eq!(events.last(), Some(&HostedNetworkEvent::Stopped));
+    }
+
+    #[test]
+    fn shutdown_tears_everything_down() {
+        let (message_tx, message_rx) = mpsc::channel();
+        let helper = WlanHostedNetworkBuilder::new()
+            .ssid("WiFiDirectMockNetwork")
+            .passphrase("TestingThisLibrary")
+            .auto_restart(RetryPolicy::new(3, Duration::from_millis(10)))
+            .event_buffer(8, OverflowPolicy::Block)
+            .event_sender(message_tx)
+            .build()
+            .unwrap();
+        let publisher = helper.mock_publisher();
+        helper.shutdown().unwrap();
+        assert_eq!(publisher.status(), Ok(HostedNetworkStatus::Stopped));
+        let events: Vec<_> = message_rx.try_iter().collect();
+        assert_eq!(events.last(), Some(&HostedNetworkEvent::Stopped));
+        // the status handler is gone, so nothing reaches the helper any more
+        publisher.abort(HostedNetworkError::RadioNotAvailable);
+        assert!(message_rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn every_sink_hears_every_event() {
+        let (message_tx, message_rx) = mpsc::channel();
+        let logged = Arc::new(Mutex::new(Vec::new()));
+        let log = logged.clone();
+        let helper = WlanHostedNetworkBuilder::new()
+            .ssid("WiFiDirectMockNetwork")
+            .passphrase("TestingThisLibrary")
+            .event_sender(message_tx)
+            .add_event_sink(move |event| log.lock().unwrap().push(event))
+            .build()
+            .unwrap();
+        helper.stop().unwrap();
+        let events: Vec<_> = message_rx.try_iter().collect();
+        assert_eq!(events.last(), Some(&HostedNetworkEvent::Stopped));
+        assert_eq!(*logged.lock().unwrap(), events);
+    }
+
+    #[test]
+    fn a_lone_panicking_sink_still_gets_later_events() {
+        let logged = Arc::new(Mutex::new(Vec::new()));
+        let log = logged.clone();
+        let helper = WlanHostedNetworkBuilder::new()
+            .ssid("WiFiDirectMockNetwork")
+            .passphrase("TestingThisLibrary")
+            .event_sink(move |event| {
+                if event == HostedNetworkEvent::Created {
+                    panic!("sink failed");
+                }
+                log.lock().unwrap().push(event);
+            })
+            .build()
+            .unwrap();
+        helper.stop().unwrap();
+        assert_eq!(
+            *logged.lock().unwrap(),
+            vec![
+                HostedNetworkEvent::Started {
+                    ssid: "WiFiDirectMockNetwork".to_string()
+                },
+                HostedNetworkEvent::Stopped
+            ]
+        );
+    }
+
+    #[test]
+    fn buffered_events_arrive_on_a_named_thread() {
+        let (name_tx, name_rx) = mpsc::channel();
+        let helper = WlanHostedNetworkBuilder::new()
+            .ssid("WiFiDirectMockNetwork")
+            .passphrase("TestingThisLibrary")
+            .event_buffer(8, OverflowPolicy::Block)
+            .thread_name_prefix("mock-ap")
+            .event_sink(move |_event: HostedNetworkEvent| {
+                let _ = name_tx.send(thread::current().name().map(str::to_string));
+            })
+            .build()
+            .unwrap();
+        helper.join_workers().unwrap();
+        let names: Vec<_> = name_rx.try_iter().collect();
+        assert!(!names.is_empty());
+        assert!(names
+            .iter()
+            .all(|name| name.as_deref() == Some("mock-ap-events")));
+    }
+
+    #[test]
+    fn checked_start_waits_for_started() {
+        let (message_tx, message_rx) = mpsc::channel();
+        let helper = WlanHostedNetworkHelper::new_checked(
+            "WiFiDirectMockNetwork",
+            "TestingThisLibrary",
+            message_tx,
+            Duration::from_secs(5),
+        )
+        .unwrap();
+        assert!(helper.is_running());
+        assert_eq!(message_rx.try_recv(), Ok(HostedNetworkEvent::Created));
+        assert_eq!(
+            message_rx.try_recv(),
+            Ok(HostedNetworkEvent::Started {
+                ssid: "WiFiDirectMockNetwork".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn canceled_start_starts_nothing() {
+        let (message_tx, message_rx) = mpsc::channel();
+        let cancel = AtomicBool::new(true);
+        let result = WlanHostedNetworkHelper::start_cancellable(
+            "WiFiDirectMockNetwork",
+            "TestingThisLibrary",
+            message_tx,
+            &cancel,
+        );
+        assert!(matches!(result, Err(HostedNetworkError::Canceled)));
+        assert!(message_rx.try_recv().is_err());
+        // left alone, it waits for the start like build_checked()
+        cancel.store(false, Ordering::SeqCst);
+        let (message_tx, _message_rx) = mpsc::channel();
+        let helper = WlanHostedNetworkHelper::start_cancellable(
+            "WiFiDirectMockNetwork",
+            "TestingThisLibrary",
+            message_tx,
+            &cancel,
+        )
+        .unwrap();
+        assert!(helper.is_running());
+    }
+
+    #[test]
+    fn unsubscribed_handler_hears_nothing() {
+        let publisher = MockPublisher::new(
+            "WiFiDirectMockNetwork",
+            "TestingThisLibrary",
+            &AdvertisementSettings {
+                autonomous_group_owner: true,
+                discoverability: None,
+                information_elements: Vec::new(),
+                username: None,
+            },
+        );
+        let heard = Arc::new(AtomicBool::new(false));
+        let flag = heard.clone();
+        publisher
+            .subscribe(Box::new(move |_| flag.store(true, Ordering::SeqCst)))
+            .unwrap();
+        publisher.unsubscribe().unwrap();
+        publisher.start().unwrap();
+        assert!(!heard.load(Ordering::SeqCst));
+        // and the handler, with what it captured, is gone
+        assert_eq!(Arc::strong_count(&heard), 1);
+    }
+
+    #[test]
+    fn endpoints_can_be_read_from_many_threads() {
+        let (message_tx, _message_rx) = mpsc::channel();
+        let helper = mock_network(message_tx);
+        let readers: Vec<_> = (0..8)
+            .map(|n| {
+                let helper = helper.clone();
+                thread::spawn(move || {
+                    let device_id = format!("WiFiDirect#{}", n);
+                    for _ in 0..200 {
+                        assert!(matches!(
+                            helper.client_endpoints(&device_id),
+                            Err(HostedNetworkError::UnknownClient(_))
+                        ));
+                        assert!(helper.client_endpoint_pairs(&device_id).is_err());
+                        assert!(helper.client_ipv6_endpoints(&device_id).is_err());
+                        assert_eq!(helper.all_client_endpoints().unwrap(), Vec::new());
+                        let _ = helper.local_address();
+                    }
+                })
+            })
+            .collect();
+        // the registry changes under them as the network restarts
+        for _ in 0..20 {
+            helper
+                .restart("WiFiDirectMockNetwork", "TestingThisLibrary")
+                .unwrap();
+        }
+        for reader in readers {
+            reader.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn poisoned_publisher_mutex_is_recovered() {
+        let (message_tx, _message_rx) = mpsc::channel();
+        let helper = mock_network(message_tx);
+        let poisoner = helper.clone();
+        let _ = thread::spawn(move || {
+            let _publisher = poisoner.inner.publisher.lock().unwrap();
+            panic!("poisoning the publisher mutex");
+        })
+        .join();
+        helper.stop().unwrap();
+        assert_eq!(helper.status(), Ok(HostedNetworkStatus::Stopped));
+        helper
+            .restart("WiFiDirectMockNetwork", "TestingThisLibrary")
+            .unwrap();
+        assert!(helper.is_running());
+    }
+
+    #[test]
+    fn direct_ssid_gets_the_prefix() {
+        let (message_tx, message_rx) = mpsc::channel();
+        let mut builder = WlanHostedNetworkBuilder::new();
+        builder
+            .direct_ssid("xy-MockNetwork")
+            .passphrase("TestingThisLibrary")
+            .warn_without_direct_prefix(true)
+            .event_sender(message_tx);
+        let helper = builder.build().unwrap();
+        assert_eq!(helper.ssid(), "DIRECT-xy-MockNetwork");
+        drop(helper);
+        assert!(!message_rx
+            .try_iter()
+            .any(|event| matches!(event, HostedNetworkEvent::SsidPrefixWarning { .. })));
+        // the prefix leaves 25 bytes
+        builder.direct_ssid(&"a".repeat(26));
+        assert!(matches!(
+            builder.build(),
+            Err(HostedNetworkError::InvalidSsid(_))
+        ));
+        builder.ssid("WiFiDirectMockNetwork");
+        let _helper = builder.build().unwrap();
+        assert_eq!(
+            message_rx.try_recv(),
+            Ok(HostedNetworkEvent::SsidPrefixWarning {
+                ssid: "WiFiDirectMockNetwork".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn ssid_reports_the_one_given() {
+        let (message_tx, _message_rx) = mpsc::channel();
+        let helper = mock_network(message_tx);
+        assert_eq!(helper.ssid(), "WiFiDirectMockNetwork");
+        assert_eq!(helper.mock_publisher().ssid(), "WiFiDirectMockNetwork");
+    }
+
+    #[test]
+    fn ssid_reports_the_generated_one() {
+        let (message_tx, message_rx) = mpsc::channel();
+        let helper = WlanHostedNetworkBuilder::new()
+            .passphrase("TestingThisLibrary")
+            .event_sender(message_tx)
+            .build()
+            .unwrap();
+        assert_eq!(message_rx.try_recv(), Ok(HostedNetworkEvent::Created));
+        assert_eq!(
+            message_rx.try_recv(),
+            Ok(HostedNetworkEvent::Started {
+                ssid: MOCK_GENERATED_SSID.to_string()
+            })
+        );
+        assert_eq!(helper.ssid(), MOCK_GENERATED_SSID);
+        assert_eq!(
+            helper.connection_uri().unwrap(),
+            format!(
+                "WIFI:T:WPA;S:{};P:TestingThisLibrary;;",
+                MOCK_GENERATED_SSID
+            )
+        );
+    }
+
+    #[test]
+    fn new_passphrases_keep_the_generated_ssid() {
+        let (message_tx, message_rx) = mpsc::channel();
+        let helper = WlanHostedNetworkBuilder::new()
+            .passphrase("TestingThisLibrary")
+            .event_sender(message_tx)
+            .build()
+            .unwrap();
+        helper.set_passphrase("TestingThisLibraryAgain").unwrap();
+        assert_eq!(
+            helper.credentials(),
+            Credentials {
+                ssid: MOCK_GENERATED_SSID.to_string(),
+                passphrase: "TestingThisLibraryAgain".to_string(),
+            }
+        );
+        let publisher = helper.mock_publisher();
+        assert_eq!(publisher.ssid(), MOCK_GENERATED_SSID);
+        assert_eq!(publisher.passphrase(), "TestingThisLibraryAgain");
+        assert_eq!(
+            message_rx.try_iter().last(),
+            Some(HostedNetworkEvent::Started {
+                ssid: MOCK_GENERATED_SSID.to_string()
+            })
+        );
+        assert!(matches!(
+            helper.set_passphrase("short"),
+            Err(HostedNetworkError::InvalidPassphrase(_))
+        ));
+    }
+
+    #[test]
+    fn late_subscribers_see_the_start() {
+        let (message_tx, _message_rx) = mpsc::channel();
+        let helper = mock_network(message_tx);
+        assert_eq!(helper.last_status(), HostedNetworkStatus::Started);
+        let statuses = helper.subscribe();
+        helper.stop().unwrap();
+        assert_eq!(helper.last_status(), HostedNetworkStatus::Stopped);
+        assert_eq!(
+            statuses.try_iter().collect::<Vec<_>>(),
+            vec![HostedNetworkStatus::Started, HostedNetworkStatus::Stopped]
+        );
+    }
+
+    #[test]
+    fn running_network_comes_back_after_an_abort() {
+        let (message_tx, message_rx) = mpsc::channel();
+        let helper = WlanHostedNetworkBuilder::new()
+            .ssid("WiFiDirectMockNetwork")
+            .passphrase("TestingThisLibrary")
+            .auto_restart(RetryPolicy::new(1, Duration::from_millis(10)))
+            .event_sender(message_tx)
+            .build()
+            .unwrap();
+        let started = HostedNetworkEvent::Started {
+            ssid: "WiFiDirectMockNetwork".to_string(),
+        };
+        assert_eq!(message_rx.recv(), Ok(HostedNetworkEvent::Created));
+        assert_eq!(message_rx.recv(), Ok(started.clone()));
+        // what a resume from sleep looks like: not a transient error, but the network was up
+        helper
+            .mock_publisher()
+            .abort(HostedNetworkError::NoCapableAdapter);
+        let events: Vec<_> = (0..4)
+            .map(|_| message_rx.recv_timeout(Duration::from_secs(5)).unwrap())
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                HostedNetworkEvent::Aborted {
+                    error: HostedNetworkError::NoCapableAdapter
+                },
+                HostedNetworkEvent::PowerResumeRestart { attempt: 1 },
+                HostedNetworkEvent::Created,
+                started,
+            ]
+        );
+        assert!(helper.is_running());
+    }
+
+    #[test]
+    fn pausing_keeps_the_network_up() {
+        let (message_tx, message_rx) = mpsc::channel();
+        let helper = mock_network(message_tx);
+        helper.pause_advertising().unwrap();
+        helper.pause_advertising().unwrap();
+        assert!(helper.is_running());
+        helper.resume_advertising().unwrap();
+        helper.resume_advertising().unwrap();
+        // past Created and Started
+        let events: Vec<_> = message_rx.try_iter().skip(2).collect();
+        assert_eq!(
+            events,
+            vec![
+                HostedNetworkEvent::AdvertisingPaused,
+                HostedNetworkEvent::AdvertisingResumed
+            ]
+        );
+    }
+
+    #[test]
+    fn abort_is_reported() {
+        let (message_tx, message_rx) = mpsc::channel();
+        let helper = mock_network(message_tx);
+        helper
+            .mock_publisher()
+            .abort(HostedNetworkError::RadioNotAvailable);
+        assert_eq!(helper.status(), Ok(HostedNetworkStatus::Aborted));
+        assert_eq!(
+            message_rx.try_iter().last(),
+            Some(HostedNetworkEvent::Aborted {
+                error: HostedNetworkError::RadioNotAvailable
+            })
+        );
+    }
+
+    #[test]
+    fn fake_peer_goes_through_the_client_events() {
+        let (message_tx, message_rx) = mpsc::channel();
+        let helper = mock_network(message_tx);
+        message_rx.try_iter().for_each(drop);
+        helper.mock_connection_request(PEER, "Pixel 7").unwrap();
+        assert_eq!(
+            message_rx.try_iter().collect::<Vec<_>>(),
+            vec![
+                HostedNetworkEvent::ConnectionRequested {
+                    device_id: PEER.to_string(),
+                    name: "Pixel 7".to_string(),
+                    wps_method: WpsMethod::None,
+                },
+                HostedNetworkEvent::ClientConnected {
+                    device_id: PEER.to_string(),
+                    display_name: String::new(),
+                    endpoints_pending: true,
+                    device: DeviceInfo {
+                        id: PEER.to_string(),
+                        name: "Pixel 7".to_string(),
+                        kind: "AssociationEndpoint".to_string(),
+                        is_paired: false,
+                        can_pair: true,
+                        properties: Default::default(),
+                    },
+                },
+            ]
+        );
+        assert_eq!(helper.client_count(), 1);
+
+        helper
+            .mock_endpoints(PEER, &[pair("192.168.137.42")])
+            .unwrap();
+        assert_eq!(
+            message_rx.recv_timeout(Duration::from_secs(5)),
+            Ok(HostedNetworkEvent::ClientReady {
+                device_id: PEER.to_string(),
+                endpoints: vec!["192.168.137.42".parse().unwrap()],
+            })
+        );
+        helper
+            .mock_endpoints(PEER, &[pair("192.168.137.43")])
+            .unwrap();
+        assert_eq!(
+            helper.refresh_client_endpoints(PEER),
+            Ok(vec!["192.168.137.43".parse().unwrap()])
+        );
+        assert_eq!(
+            message_rx.try_recv(),
+            Ok(HostedNetworkEvent::ClientEndpointsChanged {
+                device_id: PEER.to_string(),
+                endpoints: vec!["192.168.137.43".parse().unwrap()],
+            })
+        );
+
+        helper.mock_disconnect(PEER).unwrap();
+        assert_eq!(
+            message_rx.try_recv(),
+            Ok(HostedNetworkEvent::ClientDisconnected {
+                device_id: PEER.to_string()
+            })
+        );
+        assert_eq!(helper.client_count(), 0);
+        assert_eq!(
+            helper.mock_disconnect(PEER),
+            Err(HostedNetworkError::UnknownClient(PEER.to_string()))
+        );
+    }
+
+    #[test]
+    fn fake_peers_are_admitted_like_real_ones() {
+        let (message_tx, message_rx) = mpsc::channel();
+        let helper = WlanHostedNetworkBuilder::new()
+            .ssid("WiFiDirectMockNetwork")
+            .passphrase("TestingThisLibrary")
+            .max_clients(1)
+            .reconnect_window(Duration::from_secs(60))
+            .connection_filter(|device_id| !device_id.ends_with("ff"))
+            .event_sender(message_tx)
+            .build()
+            .unwrap();
+        message_rx.try_iter().for_each(drop);
+        let other = "WiFiDirect#02:8b:21:42:ac:37";
+        let requested = |device_id: &str| HostedNetworkEvent::ConnectionRequested {
+            device_id: device_id.to_string(),
+            name: device_id.to_string(),
+            wps_method: WpsMethod::None,
+        };
+        let rejected = |device_id: &str| HostedNetworkEvent::ConnectionRejected {
+            device_id: device_id.to_string(),
+        };
+
+        // turned away by the filter
+        helper
+            .mock_connection_request("WiFiDirect#02:8b:21:42:ac:ff", "")
+            .unwrap();
+        assert_eq!(
+            message_rx.try_iter().collect::<Vec<_>>(),
+            vec![
+                requested("WiFiDirect#02:8b:21:42:ac:ff"),
+                rejected("WiFiDirect#02:8b:21:42:ac:ff"),
+            ]
+        );
+
+        // the first peer fills the network, so the next is turned away
+        helper.mock_connection_request(PEER, "").unwrap();
+        helper.mock_connection_request(other, "").unwrap();
+        let events: Vec<_> = message_rx.try_iter().collect();
+        assert_eq!(events[0], requested(PEER));
+        assert!(matches!(
+            &events[1],
+            HostedNetworkEvent::ClientConnected { device_id, .. } if device_id == PEER
+        ));
+        assert_eq!(
+            events[2..],
+            [
+                HostedNetworkEvent::MaxClientsReached { max_clients: 1 },
+                requested(other),
+                rejected(other),
+            ]
+        );
+
+        // back within the reconnect window, it's the same connection
+        helper.mock_disconnect(PEER).unwrap();
+        helper.mock_connection_request(PEER, "").unwrap();
+        assert_eq!(
+            message_rx.try_iter().collect::<Vec<_>>(),
+            vec![
+                HostedNetworkEvent::ClientDisconnected {
+                    device_id: PEER.to_string()
+                },
+                requested(PEER),
+                HostedNetworkEvent::ClientReconnected {
+                    device_id: PEER.to_string(),
+                    display_name: String::new(),
+                },
+                HostedNetworkEvent::MaxClientsReached { max_clients: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn waiting_for_a_client_returns_it() {
+        let (message_tx, _message_rx) = mpsc::channel();
+        let helper = mock_network(message_tx);
+        assert_eq!(
+            helper.wait_for_client_id(PEER, Duration::from_millis(10)),
+            Err(HostedNetworkError::Timeout)
+        );
+        let connecting = helper.clone();
+        let before = std::time::Instant::now();
+        let peer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            connecting.mock_connection_request(PEER, "Pixel 7").unwrap();
+        });
+        let client = helper
+            .wait_for_client_id(PEER, Duration::from_secs(5))
+            .unwrap();
+        peer.join().unwrap();
+        assert_eq!(client.device_id, PEER);
+        assert_eq!(client.device.name, "Pixel 7");
+        assert!(client.connected_at >= before);
+    }
+
+    #[test]
+    fn operating_channel_is_never_reported() {
+        let (message_tx, _message_rx) = mpsc::channel();
+        let helper = mock_network(message_tx);
+        assert_eq!(helper.operating_channel(), Ok(None));
+        // not even once a peer is on the channel
+        helper.mock_connection_request(PEER, "Pixel 7").unwrap();
+        assert_eq!(helper.operating_channel(), Ok(None));
+    }
+
+    #[test]
+    fn socket_addrs_cover_every_address_of_every_peer() {
+        let (message_tx, _message_rx) = mpsc::channel();
+        let helper = mock_network(message_tx);
+        assert_eq!(helper.client_socket_addrs(8080), Ok(Vec::new()));
+        let other = "WiFiDirect#02:8b:21:42:ac:37";
+        helper.mock_connection_request(PEER, "Pixel 7").unwrap();
+        helper.mock_connection_request(other, "").unwrap();
+        helper
+            .mock_endpoints(
+                PEER,
+                &[
+                    pair("192.168.137.42"),
+                    EndpointPair {
+                        local: "fe80::1".parse().unwrap(),
+                        remote: "fe80::42".parse().unwrap(),
+                    },
+                ],
+            )
+            .unwrap();
+        // the other peer has no address yet, so it's left out
+        let mut addrs = helper.client_socket_addrs(8080).unwrap();
+        addrs.sort();
+        assert_eq!(
+            addrs,
+            vec![
+                (PEER.to_string(), "192.168.137.42:8080".parse().unwrap()),
+                (PEER.to_string(), "[fe80::42]:8080".parse().unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn signal_is_unreported_for_connected_peers() {
+        let (message_tx, _message_rx) = mpsc::channel();
+        let helper = mock_network(message_tx);
+        assert_eq!(helper.client_signal(PEER), Err(unknown(PEER)));
+        helper.mock_connection_request(PEER, "Pixel 7").unwrap();
+        assert_eq!(helper.client_signal(PEER), Ok(None));
+        helper.mock_disconnect(PEER).unwrap();
+        assert_eq!(helper.client_signal(PEER), Err(unknown(PEER)));
+    }
+
+    #[test]
+    fn bind_tcp_waits_for_a_client_address() {
+        let (message_tx, _message_rx) = mpsc::channel();
+        let helper = mock_network(message_tx);
+        let error = helper.bind_tcp(0).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::AddrNotAvailable);
+        assert!(error.to_string().contains("ClientReady"));
+        // the local end of a peer's pair is this PC's address on the network
+        helper.mock_connection_request(PEER, "Pixel 7").unwrap();
+        helper
+            .mock_endpoints(
+                PEER,
+                &[EndpointPair {
+                    local: "127.0.0.1".parse().unwrap(),
+                    remote: "127.0.0.2".parse().unwrap(),
+                }],
+            )
+            .unwrap();
+        let listener = helper.bind_tcp(0).unwrap();
+        assert_eq!(
+            listener.local_addr().unwrap().ip(),
+            "127.0.0.1".parse::<std::net::IpAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn traffic_stats_need_a_known_adapter() {
+        let (message_tx, _message_rx) = mpsc::channel();
+        let helper = mock_network(message_tx);
+        assert_eq!(helper.interface_stats(), Ok(None));
+        assert_eq!(helper.client_stats(PEER), Err(unknown(PEER)));
+        // a mock peer is on no adapter, so there's still nothing to count traffic on
+        helper.mock_connection_request(PEER, "Pixel 7").unwrap();
+        helper
+            .mock_endpoints(PEER, &[pair("192.168.137.42")])
+            .unwrap();
+        assert_eq!(helper.interface_stats(), Ok(None));
+        assert_eq!(helper.client_stats(PEER), Ok(None));
+        helper.stop().unwrap();
+        assert_eq!(helper.interface_stats(), Ok(None));
+    }
+
+    #[test]
+    fn refreshing_endpoints_reports_only_changes() {
+        let (message_tx, message_rx) = mpsc::channel();
+        let helper = mock_network(message_tx);
+        assert_eq!(helper.refresh_client_endpoints(PEER), Err(unknown(PEER)));
+        helper.mock_connection_request(PEER, "Pixel 7").unwrap();
+        helper
+            .mock_endpoints(PEER, &[pair("192.168.137.42")])
+            .unwrap();
+        // wait for the first address to be reported
+        loop {
+            match message_rx.recv_timeout(Duration::from_secs(5)) {
+                Ok(HostedNetworkEvent::ClientReady { .. }) => break,
+                Ok(_) => {}
+                Err(error) => panic!("no ClientReady: {}", error),
+            }
+        }
+        assert_eq!(
+            helper.refresh_client_endpoints(PEER),
+            Ok(vec!["192.168.137.42".parse().unwrap()])
+        );
+        assert!(message_rx.try_recv().is_err());
+        helper
+            .mock_endpoints(PEER, &[pair("192.168.137.43")])
+            .unwrap();
+        helper.refresh_client_endpoints(PEER).unwrap();
+        assert_eq!(
+            message_rx.try_recv(),
+            Ok(HostedNetworkEvent::ClientEndpointsChanged {
+                device_id: PEER.to_string(),
+                endpoints: vec!["192.168.137.43".parse().unwrap()],
+            })
+        );
+        helper.mock_disconnect(PEER).unwrap();
+        assert_eq!(helper.refresh_client_endpoints(PEER), Err(unknown(PEER)));
+    }
+
+    #[test]
+    fn refreshing_a_peer_without_endpoints_makes_it_ready() {
+        let (message_tx, message_rx) = mpsc::channel();
+        let helper = mock_network(message_tx);
+        helper.mock_connection_request(PEER, "Pixel 7").unwrap();
+        assert_eq!(helper.refresh_client_endpoints(PEER), Ok(Vec::new()));
+        // past the ready check's window, so only the refresh can notice the address
+        thread::sleep(Duration::from_millis(1500));
+        helper
+            .mock_endpoints(PEER, &[pair("192.168.137.42")])
+            .unwrap();
+        let endpoints = vec!["192.168.137.42".parse().unwrap()];
+        assert_eq!(helper.refresh_client_endpoints(PEER), Ok(endpoints.clone()));
+        let reports: Vec<_> = message_rx
+            .try_iter()
+            .filter(|event| {
+                matches!(
+                    event,
+                    HostedNetworkEvent::ClientReady { .. }
+                        | HostedNetworkEvent::ClientEndpointsChanged { .. }
+                )
+            })
+            .collect();
+        assert_eq!(
+            reports,
+            vec![HostedNetworkEvent::ClientReady {
+                device_id: PEER.to_string(),
+                endpoints,
+            }]
+        );
+    }
+
+    #[test]
+    fn disconnecting_a_client_reports_it() {
+        let (message_tx, message_rx) = mpsc::channel();
+        let helper = mock_network(message_tx);
+        helper.mock_connection_request(PEER, "Pixel 7").unwrap();
+        message_rx.try_iter().for_each(drop);
+        helper.disconnect_client(PEER).unwrap();
+        assert_eq!(
+            message_rx.try_recv(),
+            Ok(HostedNetworkEvent::ClientDisconnected {
+                device_id: PEER.to_string()
+            })
+        );
+        assert!(!helper.is_client_connected(PEER));
+        assert_eq!(helper.disconnect_client(PEER), Err(unknown(PEER)));
+    }
+}
@@ -0,0 +1,46 @@
+use crate::validate::{MAX_PASSPHRASE_CHARS, MIN_PASSPHRASE_CHARS};
+
+const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// Generates a random alphanumeric passphrase from the operating system's secure random number
+/// generator. `len` is clamped to the 8 to 63 characters WPA2 allows.
+///
+/// Panics if the operating system can't provide random bytes.
+pub fn random_passphrase(len: usize) -> String {
+    let len = len.clamp(MIN_PASSPHRASE_CHARS, MAX_PASSPHRASE_CHARS);
+    // Bytes at or above the largest multiple of the alphabet size are thrown away so every
+    // character is equally likely.
+    let limit = (u8::MAX as usize + 1) / ALPHABET.len() * ALPHABET.len();
+    let mut passphrase = String::with_capacity(len);
+    let mut buf = [0u8; 64];
+    while passphrase.len() < len {
+        getrandom::getrandom(&mut buf).expect("Couldn't get random bytes from the OS");
+        for &byte in buf.iter().filter(|&&byte| (byte as usize) < limit) {
+            if passphrase.len() == len {
+                break;
+            }
+            passphrase.push(ALPHABET[byte as usize % ALPHABET.len()] as char);
+        }
+    }
+    passphrase
+}
+
+#[cfg(test)]
+mod tests {
+    use super::random_passphrase;
+
+    #[test]
+    fn length_is_clamped() {
+        assert_eq!(random_passphrase(0).len(), 8);
+        assert_eq!(random_passphrase(20).len(), 20);
+        assert_eq!(random_passphrase(100).len(), 63);
+    }
+
+    #[test]
+    fn only_alphanumeric() {
+        assert!(random_passphrase(63)
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric()));
+        assert_ne!(random_passphrase(20), random_passphrase(20));
+    }
+}
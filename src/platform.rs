@@ -0,0 +1,187 @@
+//! Everything this crate uses from the `windows` bindings comes in through here, so moving to a
+//! new version of them should only mean changing this file. The publisher's status, its errors,
+//! Windows Runtime errors and GUIDs are mapped onto the crate's own types at this boundary, so
+//! the only `windows` type in the public API is the publisher the `winrt` module hands out.
+
+// public so `winrt` can re-export it
+pub use windows::Devices::WiFiDirect::WiFiDirectAdvertisementPublisher;
+
+pub(crate) use windows::core::{Error, IInspectable, Interface, Result, GUID, HRESULT, HSTRING};
+pub(crate) use windows::Devices::Enumeration::{DeviceInformation, DeviceInformationKind};
+pub(crate) use windows::Devices::WiFi::WiFiAdapter;
+pub(crate) use windows::Devices::WiFiDirect::{
+    WiFiDirectAdvertisementListenStateDiscoverability, WiFiDirectAdvertisementPublisherStatus,
+    WiFiDirectAdvertisementPublisherStatusChangedEventArgs, WiFiDirectConnectionListener,
+    WiFiDirectConnectionParameters, WiFiDirectConnectionRequestedEventArgs,
+    WiFiDirectConnectionStatus, WiFiDirectDevice, WiFiDirectError, WiFiDirectInformationElement,
+};
+pub(crate) use windows::Foundation::{
+    AsyncOperationCompletedHandler, AsyncStatus, DateTime, EventRegistrationToken, IAsyncOperation,
+    IPropertyValue, PropertyType, TypedEventHandler,
+};
+pub(crate) use windows::Networking::Connectivity::{
+    DataUsageGranularity, NetworkInformation, NetworkUsageStates, TriStates,
+};
+pub(crate) use windows::Security::Credentials::PasswordCredential;
+pub(crate) use windows::Storage::Streams::{DataWriter, IBuffer};
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{Discoverability, Guid, HostedNetworkError, HostedNetworkStatus, WindowsError};
+
+impl From<Error> for HostedNetworkError {
+    fn from(error: Error) -> Self {
+        Self::Windows(WindowsError {
+            code: error.code().0,
+            message: error.message().to_string(),
+        })
+    }
+}
+
+impl From<GUID> for Guid {
+    fn from(guid: GUID) -> Self {
+        Guid(guid.to_u128())
+    }
+}
+
+/// Maps a `WiFiDirectAdvertisementPublisherStatus` onto `HostedNetworkStatus`.
+pub(crate) fn publisher_status(
+    status: WiFiDirectAdvertisementPublisherStatus,
+) -> std::result::Result<HostedNetworkStatus, HostedNetworkError> {
+    match status {
+        WiFiDirectAdvertisementPublisherStatus::Created => Ok(HostedNetworkStatus::Created),
+        WiFiDirectAdvertisementPublisherStatus::Started => Ok(HostedNetworkStatus::Started),
+        WiFiDirectAdvertisementPublisherStatus::Stopped => Ok(HostedNetworkStatus::Stopped),
+        WiFiDirectAdvertisementPublisherStatus::Aborted => Ok(HostedNetworkStatus::Aborted),
+        status => Err(HostedNetworkError::UnknownStatus(status.0)),
+    }
+}
+
+pub(crate) fn listen_state(
+    discoverability: Discoverability,
+) -> WiFiDirectAdvertisementListenStateDiscoverability {
+    match discoverability {
+        Discoverability::None => WiFiDirectAdvertisementListenStateDiscoverability::None,
+        Discoverability::Normal => WiFiDirectAdvertisementListenStateDiscoverability::Normal,
+        Discoverability::Intensive => WiFiDirectAdvertisementListenStateDiscoverability::Intensive,
+    }
+}
+
+/// Names a `DeviceInformationKind` the way the WinRT documentation does.
+pub(crate) fn device_kind(kind: DeviceInformationKind) -> String {
+    match kind {
+        DeviceInformationKind::DeviceInterface => "DeviceInterface".to_string(),
+        DeviceInformationKind::DeviceContainer => "DeviceContainer".to_string(),
+        DeviceInformationKind::Device => "Device".to_string(),
+        DeviceInformationKind::DeviceInterfaceClass => "DeviceInterfaceClass".to_string(),
+        DeviceInformationKind::AssociationEndpoint => "AssociationEndpoint".to_string(),
+        DeviceInformationKind::AssociationEndpointContainer => {
+            "AssociationEndpointContainer".to_string()
+        }
+        DeviceInformationKind::AssociationEndpointService => {
+            "AssociationEndpointService".to_string()
+        }
+        DeviceInformationKind::DevicePanel => "DevicePanel".to_string(),
+        _ => "Unknown".to_string(),
+    }
+}
+
+/// Reads a device property as a string, if it holds one or a list of them. Lists are joined with
+/// `;`, the way Windows shows them.
+pub(crate) fn property_string(value: &IInspectable) -> Option<String> {
+    let value = value.cast::<IPropertyValue>().ok()?;
+    match value.Type().ok()? {
+        PropertyType::String => Some(value.GetString().ok()?.to_string()),
+        PropertyType::StringArray => {
+            let mut strings = windows::core::Array::new();
+            value.GetStringArray(&mut strings).ok()?;
+            Some(
+                strings
+                    .iter()
+                    .map(HSTRING::to_string)
+                    .collect::<Vec<_>>()
+                    .join(";"),
+            )
+        }
+        PropertyType::Boolean => Some(value.GetBoolean().ok()?.to_string()),
+        _ => None,
+    }
+}
+
+// seconds from 1601-01-01, where `DateTime` counts from, to the Unix epoch
+const UNIX_EPOCH_SECONDS: u64 = 11_644_473_600;
+
+/// Converts a `SystemTime` to a `DateTime`, which counts 100 ns ticks since 1601-01-01 UTC.
+pub(crate) fn date_time(time: SystemTime) -> DateTime {
+    let since_1601 = match time.duration_since(UNIX_EPOCH) {
+        Ok(after) => Duration::from_secs(UNIX_EPOCH_SECONDS) + after,
+        Err(error) => Duration::from_secs(UNIX_EPOCH_SECONDS).saturating_sub(error.duration()),
+    };
+    DateTime {
+        UniversalTime: (since_1601.as_nanos() / 100) as i64,
+    }
+}
+
+/// Copies bytes into a WinRT buffer.
+pub(crate) fn buffer(bytes: &[u8]) -> Result<IBuffer> {
+    let writer = DataWriter::new()?;
+    writer.WriteBytes(bytes)?;
+    writer.DetachBuffer()
+}
+
+/// Maps the error reported by an aborted `WiFiDirectAdvertisementPublisher`.
+pub(crate) fn wifi_direct_error(error: WiFiDirectError) -> HostedNetworkError {
+    match error {
+        WiFiDirectError::RadioNotAvailable => HostedNetworkError::RadioNotAvailable,
+        WiFiDirectError::ResourceInUse => HostedNetworkError::ResourceInUse,
+        WiFiDirectError::Success => HostedNetworkError::NoCapableAdapter,
+        WiFiDirectError(code) => HostedNetworkError::UnknownWiFiDirectError(code),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        date_time, publisher_status, wifi_direct_error, WiFiDirectAdvertisementPublisherStatus,
+        WiFiDirectError,
+    };
+    use crate::{HostedNetworkError, HostedNetworkStatus};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn maps_publisher_status() {
+        assert_eq!(
+            publisher_status(WiFiDirectAdvertisementPublisherStatus::Started),
+            Ok(HostedNetworkStatus::Started)
+        );
+        assert_eq!(
+            publisher_status(WiFiDirectAdvertisementPublisherStatus(9)),
+            Err(HostedNetworkError::UnknownStatus(9))
+        );
+    }
+
+    #[test]
+    fn maps_wifi_direct_errors() {
+        assert_eq!(
+            wifi_direct_error(WiFiDirectError::ResourceInUse),
+            HostedNetworkError::ResourceInUse
+        );
+        assert_eq!(
+            wifi_direct_error(WiFiDirectError(42)),
+            HostedNetworkError::UnknownWiFiDirectError(42)
+        );
+    }
+
+    #[test]
+    fn converts_to_date_time() {
+        assert_eq!(date_time(UNIX_EPOCH).UniversalTime, 116_444_736_000_000_000);
+        assert_eq!(
+            date_time(UNIX_EPOCH + Duration::from_millis(1)).UniversalTime,
+            116_444_736_000_010_000
+        );
+        assert_eq!(
+            date_time(UNIX_EPOCH - Duration::from_secs(1)).UniversalTime,
+            116_444_735_990_000_000
+        );
+    }
+}
@@ -0,0 +1,228 @@
+//! The operations the crate needs from a Wi-Fi Direct advertisement publisher. The real one wraps
+//! `WiFiDirectAdvertisementPublisher`; with the `mock` feature every network gets a
+//! `MockPublisher` instead, so the event flow can be tested without a Wi-Fi Direct adapter, or
+//! without Windows at all.
+
+#[cfg(windows)]
+use std::sync::{Mutex, PoisonError};
+
+use crate::builder::AdvertisementSettings;
+use crate::{HostedNetworkError, HostedNetworkStatus, Result};
+
+#[cfg(windows)]
+use crate::platform::{
+    self, EventRegistrationToken, PasswordCredential, TypedEventHandler,
+    WiFiDirectAdvertisementPublisher, WiFiDirectAdvertisementPublisherStatusChangedEventArgs,
+    WiFiDirectInformationElement, HSTRING,
+};
+
+/// A status the publisher has moved to.
+pub(crate) struct StatusChange {
+    pub(crate) status: HostedNetworkStatus,
+    /// Why the publisher aborted, when `status` is `Aborted`.
+    pub(crate) error: Option<HostedNetworkError>,
+    /// The SSID the network is up under, when `status` is `Started`.
+    pub(crate) ssid: Option<String>,
+}
+
+/// Called on every status change, or with the reason a change couldn't be read.
+pub(crate) type StatusHandler = Box<dyn FnMut(Result<StatusChange>) + Send>;
+
+pub(crate) trait Publisher: Send {
+    /// Starts advertising. The outcome arrives through the subscribed handler.
+    fn start(&self) -> Result<()>;
+    fn stop(&self) -> Result<()>;
+    fn status(&self) -> Result<HostedNetworkStatus>;
+    /// The SSID being advertised. When none was given, Windows picks one when the publisher
+    /// starts, and this is empty until then.
+    fn ssid(&self) -> Result<String>;
+    /// Registers the handler for status changes. Call before `start()` so none are missed.
+    fn subscribe(&self, handler: StatusHandler) -> Result<()>;
+    /// Removes the handler registered by `subscribe()`, so it and everything it holds can be
+    /// dropped even if Windows keeps the publisher alive.
+    fn unsubscribe(&self) -> Result<()>;
+
+    /// Whether peers can reach the network, so a `WiFiDirectConnectionListener` should be
+    /// started alongside it. A fake publisher has no radio for them to find, and the listener
+    /// would need the Wi-Fi Direct runtime it is there to do without.
+    fn accepts_connections(&self) -> bool {
+        true
+    }
+
+    /// The WinRT publisher underneath, if there is one.
+    #[cfg(windows)]
+    fn winrt(&self) -> Option<&WiFiDirectAdvertisementPublisher> {
+        None
+    }
+
+    #[cfg(all(test, feature = "mock"))]
+    fn as_mock(&self) -> Option<&crate::mock::MockPublisher> {
+        None
+    }
+}
+
+/// Makes a publisher configured for a legacy access point with these credentials and settings.
+#[cfg(any(windows, feature = "mock"))]
+pub(crate) fn create(
+    ssid: &str,
+    password: &str,
+    settings: &AdvertisementSettings,
+) -> Result<Box<dyn Publisher>> {
+    #[cfg(not(feature = "mock"))]
+    let publisher = WinRtPublisher::new(ssid, password, settings)?;
+    #[cfg(feature = "mock")]
+    let publisher = crate::mock::MockPublisher::new(ssid, password, settings);
+    Ok(Box::new(publisher))
+}
+
+/// Hosted networks need Windows, so elsewhere only the `mock` feature has a publisher to offer.
+#[cfg(not(any(windows, feature = "mock")))]
+pub(crate) fn create(
+    _ssid: &str,
+    _password: &str,
+    _settings: &AdvertisementSettings,
+) -> Result<Box<dyn Publisher>> {
+    Err(HostedNetworkError::UnsupportedPlatform)
+}
+
+// unused with the mock feature, but still compiled on Windows so it's checked there in every
+// configuration
+#[cfg(windows)]
+#[cfg_attr(feature = "mock", allow(dead_code))]
+struct WinRtPublisher {
+    publisher: WiFiDirectAdvertisementPublisher,
+    status_changed: Mutex<Option<EventRegistrationToken>>,
+}
+
+#[cfg(windows)]
+#[cfg_attr(feature = "mock", allow(dead_code))]
+impl WinRtPublisher {
+    fn new(ssid: &str, password: &str, settings: &AdvertisementSettings) -> Result<Self> {
+        let publisher = WiFiDirectAdvertisementPublisher::new()?;
+        debug!("Created WiFiDirectAdvertisementPublisher for {}", ssid);
+
+        // set advertisement required settings
+        let advertisement = publisher.Advertisement()?;
+        advertisement.SetIsAutonomousGroupOwnerEnabled(settings.autonomous_group_owner)?;
+        if let Some(discoverability) = settings.discoverability {
+            advertisement.SetListenStateDiscoverability(platform::listen_state(discoverability))?;
+        }
+        if !settings.information_elements.is_empty() {
+            let elements = advertisement.InformationElements()?;
+            for element in &settings.information_elements {
+                let information_element = WiFiDirectInformationElement::new()?;
+                information_element.SetOui(&platform::buffer(&element.oui)?)?;
+                information_element.SetOuiType(element.oui_type)?;
+                information_element.SetValue(&platform::buffer(&element.data)?)?;
+                elements.Append(&information_element)?;
+            }
+        }
+
+        // set ssid and password
+        let legacy_settings = advertisement.LegacySettings()?;
+        legacy_settings.SetIsEnabled(true)?;
+        // left unset, Windows makes one up in the DIRECT-xy-<PC name> style
+        if !ssid.is_empty() {
+            legacy_settings.SetSsid(&HSTRING::from(ssid))?;
+        }
+        let password_credential = PasswordCredential::new()?;
+        if let Some(username) = &settings.username {
+            password_credential.SetUserName(&HSTRING::from(username))?;
+        }
+        password_credential.SetPassword(&HSTRING::from(password))?;
+        legacy_settings.SetPassphrase(&password_credential)?;
+
+        Ok(WinRtPublisher {
+            publisher,
+            status_changed: Mutex::new(None),
+        })
+    }
+}
+
+#[cfg(windows)]
+impl Publisher for WinRtPublisher {
+    fn start(&self) -> Result<()> {
+        // Start the advertisement, which will create an access point that other peers can connect to
+        Ok(self.publisher.Start()?)
+    }
+
+    fn stop(&self) -> Result<()> {
+        Ok(self.publisher.Stop()?)
+    }
+
+    fn status(&self) -> Result<HostedNetworkStatus> {
+        platform::publisher_status(self.publisher.Status()?)
+    }
+
+    fn ssid(&self) -> Result<String> {
+        legacy_ssid(&self.publisher)
+    }
+
+    fn subscribe(&self, mut handler: StatusHandler) -> Result<()> {
+        let callback = TypedEventHandler::<
+            WiFiDirectAdvertisementPublisher,
+            WiFiDirectAdvertisementPublisherStatusChangedEventArgs,
+        >::new(move |sender, args| {
+            handler(status_change(sender, args));
+            Ok(())
+        });
+        let token = self.publisher.StatusChanged(&callback)?;
+        *self
+            .status_changed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(token);
+        Ok(())
+    }
+
+    fn unsubscribe(&self) -> Result<()> {
+        let token = self
+            .status_changed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(token) = token {
+            self.publisher.RemoveStatusChanged(token)?;
+        }
+        Ok(())
+    }
+
+    fn winrt(&self) -> Option<&WiFiDirectAdvertisementPublisher> {
+        Some(&self.publisher)
+    }
+}
+
+#[cfg(windows)]
+#[cfg_attr(feature = "mock", allow(dead_code))]
+fn legacy_ssid(publisher: &WiFiDirectAdvertisementPublisher) -> Result<String> {
+    Ok(publisher
+        .Advertisement()?
+        .LegacySettings()?
+        .Ssid()?
+        .to_string())
+}
+
+#[cfg(windows)]
+#[cfg_attr(feature = "mock", allow(dead_code))]
+fn status_change(
+    sender: &Option<WiFiDirectAdvertisementPublisher>,
+    args: &Option<WiFiDirectAdvertisementPublisherStatusChangedEventArgs>,
+) -> Result<StatusChange> {
+    let args = args.as_ref().ok_or(HostedNetworkError::missing_event_args(
+        "status change callback",
+    ))?;
+    let status = platform::publisher_status(args.Status()?)?;
+    let error = match status {
+        HostedNetworkStatus::Aborted => Some(platform::wifi_direct_error(args.Error()?)),
+        _ => None,
+    };
+    // read back rather than remembered, in case Windows chose it
+    let ssid = match (status, sender) {
+        (HostedNetworkStatus::Started, Some(publisher)) => Some(legacy_ssid(publisher)?),
+        _ => None,
+    };
+    Ok(StatusChange {
+        status,
+        error,
+        ssid,
+    })
+}
@@ -0,0 +1,139 @@
+use std::io;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::sync;
+
+/// How long an `on_connection_request` callback gets before the peer is turned away.
+pub const CONNECTION_DECISION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What the builder's `on_connection_request` callback is told about a peer asking to connect.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConnectionRequestInfo {
+    pub device_id: String,
+    /// The peer's friendly name, or its device ID if it doesn't have one.
+    pub name: String,
+    /// Whether Windows has already paired with the peer.
+    pub is_paired: bool,
+    /// Whether Windows could pair with the peer.
+    pub can_pair: bool,
+}
+
+/// The WPS configuration method a peer started its connection with, as sent with
+/// `ConnectionRequested`.
+///
+/// Legacy clients join with the WPA2 passphrase rather than WPS, and the connection request
+/// Windows hands this crate carries only the peer's `DeviceInformation`, not how it is
+/// configuring. So this is always `None` for now, and a UI shouldn't wait on a push-button or PIN
+/// prompt because of it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WpsMethod {
+    PushButton,
+    Pin,
+    #[default]
+    None,
+}
+
+/// Whether to go ahead with a connection, as returned by the `on_connection_request` callback.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ConnectionDecision {
+    Accept,
+    Reject,
+}
+
+pub(crate) type ConnectionCallback =
+    Arc<dyn Fn(ConnectionRequestInfo) -> ConnectionDecision + Send + Sync>;
+
+/// Runs `callback` on its own thread, named `thread_name`, so the WinRT thread waits at most
+/// `timeout` for it. A callback that runs out of time or panics counts as `Reject`. Fails if the
+/// thread can't be started, in which case the callback never runs.
+pub(crate) fn decide(
+    callback: &ConnectionCallback,
+    info: ConnectionRequestInfo,
+    timeout: Duration,
+    thread_name: String,
+) -> io::Result<ConnectionDecision> {
+    let (decision_tx, decision_rx) = mpsc::channel();
+    let callback = callback.clone();
+    let device_id = info.device_id.clone();
+    sync::spawn(thread_name, move || {
+        // nobody is listening any more if the decision took too long
+        let _ = decision_tx.send(callback(info));
+    })?;
+    Ok(match decision_rx.recv_timeout(timeout) {
+        Ok(decision) => decision,
+        Err(mpsc::RecvTimeoutError::Timeout) => {
+            warn!(
+                "Connection callback took over {:?} for {}, rejecting",
+                timeout, device_id
+            );
+            ConnectionDecision::Reject
+        }
+        Err(mpsc::RecvTimeoutError::Disconnected) => {
+            warn!("Connection callback panicked for {}, rejecting", device_id);
+            ConnectionDecision::Reject
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decide, ConnectionCallback, ConnectionDecision, ConnectionRequestInfo};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    fn info() -> ConnectionRequestInfo {
+        ConnectionRequestInfo {
+            device_id: "WiFiDirect#02:8b:21:42:ac:36".to_string(),
+            name: "Pixel 7".to_string(),
+            is_paired: false,
+            can_pair: true,
+        }
+    }
+
+    #[test]
+    fn slow_or_panicking_callbacks_reject() {
+        let accept: ConnectionCallback = Arc::new(|info| {
+            assert_eq!(info.name, "Pixel 7");
+            ConnectionDecision::Accept
+        });
+        assert_eq!(
+            decide(
+                &accept,
+                info(),
+                Duration::from_secs(5),
+                "test-request".to_string()
+            )
+            .unwrap(),
+            ConnectionDecision::Accept
+        );
+        let slow: ConnectionCallback = Arc::new(|_| {
+            thread::sleep(Duration::from_millis(500));
+            ConnectionDecision::Accept
+        });
+        assert_eq!(
+            decide(
+                &slow,
+                info(),
+                Duration::from_millis(10),
+                "test-request".to_string()
+            )
+            .unwrap(),
+            ConnectionDecision::Reject
+        );
+        let panicking: ConnectionCallback = Arc::new(|_| panic!("database unavailable"));
+        assert_eq!(
+            decide(
+                &panicking,
+                info(),
+                Duration::from_secs(5),
+                "test-request".to_string()
+            )
+            .unwrap(),
+            ConnectionDecision::Reject
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use std::io;
+use std::sync::mpsc::Receiver;
+use std::sync::Weak;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::sync;
+use crate::{report_error, HostedNetworkError, HostedNetworkEvent, Shared};
+
+/// How `WlanHostedNetworkBuilder::auto_restart` brings an aborted network back up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RetryPolicy {
+    /// Restarts to attempt before giving up. The count resets once the network starts again.
+    pub max_attempts: u32,
+    /// How long to wait after an abort before restarting.
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32, backoff: Duration) -> Self {
+        RetryPolicy {
+            max_attempts,
+            backoff,
+        }
+    }
+
+    /// Number of the restart to make after `error`, given how many have been made since the
+    /// network last started, or `None` if it should stay down. A network that was up when it
+    /// aborted is restarted whatever the error, since that's how an adapter reset looks.
+    pub(crate) fn next_attempt(
+        &self,
+        attempts: u32,
+        error: &HostedNetworkError,
+        was_running: bool,
+    ) -> Option<u32> {
+        if (was_running || error.is_transient()) && attempts < self.max_attempts {
+            Some(attempts + 1)
+        } else {
+            None
+        }
+    }
+}
+
+/// What the restart worker is told.
+pub(crate) enum RestartSignal {
+    /// Whether a start succeeded.
+    Outcome(Result<(), HostedNetworkError>),
+    /// A network that had started aborted, as it does when the adapter resets on resume.
+    Dropped(HostedNetworkError),
+    /// The helper is shutting down, so the worker should exit.
+    Shutdown,
+}
+
+/// Restarts the publisher after transient aborts until the policy runs out. Each start reports
+/// whether it succeeded on `signals`; the worker exits on `RestartSignal::Shutdown`, or once the
+/// helper and its publishers are gone and the channel closes.
+pub(crate) fn spawn_restart_worker(
+    policy: RetryPolicy,
+    signals: Receiver<RestartSignal>,
+    shared: Weak<Shared>,
+    thread_name: String,
+) -> io::Result<JoinHandle<()>> {
+    sync::spawn(thread_name, move || {
+        let mut attempts = 0;
+        for signal in signals {
+            let (error, was_running) = match signal {
+                RestartSignal::Outcome(Ok(())) => {
+                    attempts = 0;
+                    continue;
+                }
+                RestartSignal::Outcome(Err(error)) => (error, false),
+                RestartSignal::Dropped(error) => (error, true),
+                RestartSignal::Shutdown => return,
+            };
+            let attempt = match policy.next_attempt(attempts, &error, was_running) {
+                Some(attempt) => attempt,
+                None => continue,
+            };
+            attempts = attempt;
+            // don't keep the network alive while waiting
+            match shared.upgrade() {
+                Some(shared) => {
+                    shared.plumbing.events.emit(if was_running {
+                        HostedNetworkEvent::PowerResumeRestart { attempt }
+                    } else {
+                        HostedNetworkEvent::Retrying { attempt }
+                    });
+                }
+                None => return,
+            }
+            thread::sleep(policy.backoff);
+            let shared = match shared.upgrade() {
+                Some(shared) if !shared.plumbing.is_shutting_down() => shared,
+                _ => return,
+            };
+            debug!("Restarting aborted hosted network, attempt {}", attempt);
+            if let Err(error) = shared.restart_aborted() {
+                report_error(&*shared.plumbing.events, error);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RetryPolicy;
+    use crate::HostedNetworkError;
+    use std::time::Duration;
+
+    #[test]
+    fn retries_transient_errors_up_to_the_limit() {
+        let policy = RetryPolicy::new(2, Duration::from_secs(1));
+        let error = HostedNetworkError::RadioNotAvailable;
+        assert_eq!(policy.next_attempt(0, &error, false), Some(1));
+        assert_eq!(policy.next_attempt(1, &error, false), Some(2));
+        assert_eq!(policy.next_attempt(2, &error, false), None);
+        assert_eq!(
+            policy.next_attempt(0, &HostedNetworkError::ResourceInUse, false),
+            Some(1)
+        );
+        assert_eq!(
+            policy.next_attempt(0, &HostedNetworkError::NoCapableAdapter, false),
+            None
+        );
+    }
+
+    #[test]
+    fn running_networks_are_restarted_after_any_abort() {
+        let policy = RetryPolicy::new(1, Duration::from_secs(1));
+        let error = HostedNetworkError::NoCapableAdapter;
+        assert_eq!(policy.next_attempt(0, &error, true), Some(1));
+        assert_eq!(policy.next_attempt(1, &error, true), None);
+    }
+}
@@ -0,0 +1,47 @@
+#[cfg(windows)]
+use std::collections::HashSet;
+
+#[cfg(windows)]
+use crate::platform::{WiFiAdapter, WiFiDirectAdvertisementPublisher};
+use crate::Result;
+
+/// Scans with every Wi-Fi adapter and counts the access points broadcasting `ssid`. Scanning
+/// takes a few seconds per adapter.
+#[cfg(windows)]
+pub(crate) fn count_bssids(ssid: &str) -> Result<usize> {
+    let adapters = WiFiAdapter::FindAllAdaptersAsync()?.get()?;
+    // an access point seen by two adapters is still one access point
+    let mut bssids = HashSet::new();
+    for adapter in &adapters {
+        adapter.ScanAsync()?.get()?;
+        for network in &adapter.NetworkReport()?.AvailableNetworks()? {
+            if network.Ssid()? == ssid {
+                bssids.insert(network.Bssid()?.to_string());
+            }
+        }
+    }
+    Ok(bssids.len())
+}
+
+/// Without Windows there's no `WiFiAdapter` to scan with.
+#[cfg(not(windows))]
+pub(crate) fn count_bssids(_ssid: &str) -> Result<usize> {
+    Err(crate::HostedNetworkError::UnsupportedPlatform)
+}
+
+/// Whether there's a Wi-Fi adapter and Windows can create a publisher, for
+/// `WlanHostedNetworkHelper::is_supported()`.
+#[cfg(windows)]
+pub(crate) fn is_supported() -> Result<bool> {
+    let adapters = WiFiAdapter::FindAllAdaptersAsync()?.get()?;
+    if adapters.Size()? == 0 {
+        return Ok(false);
+    }
+    // constructing a publisher doesn't start advertising
+    Ok(WiFiDirectAdvertisementPublisher::new().is_ok())
+}
+
+#[cfg(not(windows))]
+pub(crate) fn is_supported() -> Result<bool> {
+    Ok(false)
+}
@@ -0,0 +1,149 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::{HostedNetworkEvent, TimedEvent};
+
+/// Receives the events the hosted network reports. Events are emitted from Windows Runtime
+/// callback threads, so implementations shouldn't block for long.
+///
+/// Implemented for `std::sync::mpsc::Sender<HostedNetworkEvent>` and for closures, so any other
+/// channel can be plugged in with a closure that forwards to it:
+///
+/// ```no_run
+/// use wifidirect_legacy_ap::{HostedNetworkEvent, WlanHostedNetworkBuilder};
+///
+/// let hotspot = WlanHostedNetworkBuilder::new()
+///     .ssid("WiFiDirectTestNetwork")
+///     .passphrase("TestingThisLibrary")
+///     .event_sink(|event: HostedNetworkEvent| println!("{}", event))
+///     .build()
+///     .unwrap();
+/// ```
+pub trait EventSink: Send + Sync {
+    fn emit(&self, event: HostedNetworkEvent);
+
+    /// Like `emit()`, for an event that was raised at `at`. The crate calls this when handing
+    /// over an event some time after it happened, as an `event_buffer` does. The default ignores
+    /// the time and calls `emit()`.
+    fn emit_at(&self, event: HostedNetworkEvent, at: SystemTime) {
+        let _ = at;
+        self.emit(event)
+    }
+}
+
+impl<F> EventSink for F
+where
+    F: Fn(HostedNetworkEvent) + Send + Sync,
+{
+    fn emit(&self, event: HostedNetworkEvent) {
+        self(event)
+    }
+}
+
+impl EventSink for Sender<HostedNetworkEvent> {
+    fn emit(&self, event: HostedNetworkEvent) {
+        // the consumer going away first is normal, and there's nobody left to tell anyway
+        if self.send(event).is_err() {
+            debug!("Event receiver dropped, discarding event");
+        }
+    }
+}
+
+/// Sends each event with the time it was emitted, for
+/// `WlanHostedNetworkBuilder::timed_event_sender()`. Not an `EventSink` impl on the `Sender` itself
+/// so that callers' untyped channels still infer as `Sender<HostedNetworkEvent>`.
+pub(crate) struct TimedSender(pub(crate) Sender<TimedEvent>);
+
+impl EventSink for TimedSender {
+    fn emit(&self, event: HostedNetworkEvent) {
+        self.emit_at(event, SystemTime::now())
+    }
+
+    fn emit_at(&self, event: HostedNetworkEvent, at: SystemTime) {
+        if self.0.send(TimedEvent { at, event }).is_err() {
+            debug!("Event receiver dropped, discarding event");
+        }
+    }
+}
+
+/// Hands each event to the builder's sinks in the order they were added, even when there is only
+/// one. A sink that panics is logged and skipped, so the ones after it still get the event and
+/// the panic never unwinds into the Windows Runtime callback that raised it.
+pub(crate) struct FanOut(pub(crate) Vec<Arc<dyn EventSink>>);
+
+impl EventSink for FanOut {
+    fn emit(&self, event: HostedNetworkEvent) {
+        self.emit_at(event, SystemTime::now())
+    }
+
+    fn emit_at(&self, event: HostedNetworkEvent, at: SystemTime) {
+        for (index, sink) in self.0.iter().enumerate() {
+            let event = event.clone();
+            if panic::catch_unwind(AssertUnwindSafe(|| sink.emit_at(event, at))).is_err() {
+                warn!("Event sink {} panicked, skipping it for this event", index);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventSink, FanOut, TimedSender};
+    use crate::{HostedNetworkEvent, TimedEvent};
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn closures_and_senders_are_sinks() {
+        let seen = Mutex::new(Vec::new());
+        let sink = |event| seen.lock().unwrap().push(event);
+        sink.emit(HostedNetworkEvent::Created);
+        assert_eq!(*seen.lock().unwrap(), vec![HostedNetworkEvent::Created]);
+
+        let (tx, rx) = mpsc::channel();
+        tx.emit(HostedNetworkEvent::Stopped);
+        assert_eq!(rx.recv().unwrap(), HostedNetworkEvent::Stopped);
+        drop(rx);
+        tx.emit(HostedNetworkEvent::Stopped);
+    }
+
+    #[test]
+    fn timed_senders_keep_the_given_time() {
+        let (tx, rx) = mpsc::channel();
+        let tx = TimedSender(tx);
+        let at = SystemTime::now() - Duration::from_secs(5);
+        tx.emit_at(HostedNetworkEvent::Stopped, at);
+        assert_eq!(
+            rx.recv().unwrap(),
+            TimedEvent {
+                at,
+                event: HostedNetworkEvent::Stopped
+            }
+        );
+        let before = SystemTime::now();
+        tx.emit(HostedNetworkEvent::Created);
+        assert!(rx.recv().unwrap().at >= before);
+    }
+
+    #[test]
+    fn fan_out_reaches_every_sink_past_a_panic() {
+        let (first_tx, first_rx) = mpsc::channel();
+        let (second_tx, second_rx) = mpsc::channel();
+        let sinks = FanOut(vec![
+            Arc::new(first_tx),
+            Arc::new(|event| {
+                if event == HostedNetworkEvent::Stopped {
+                    panic!("sink failed");
+                }
+            }),
+            Arc::new(second_tx),
+        ]);
+        sinks.emit(HostedNetworkEvent::Created);
+        sinks.emit(HostedNetworkEvent::Stopped);
+        let expected = vec![HostedNetworkEvent::Created, HostedNetworkEvent::Stopped];
+        assert_eq!(first_rx.try_iter().collect::<Vec<_>>(), expected);
+        assert_eq!(second_rx.try_iter().collect::<Vec<_>>(), expected);
+    }
+}
@@ -0,0 +1,67 @@
+use std::time::SystemTime;
+
+#[cfg(windows)]
+use crate::platform::{
+    self, DataUsageGranularity, NetworkInformation, NetworkUsageStates, TriStates,
+};
+use crate::{Guid, Result};
+
+/// Traffic counted on the hosted network's adapter. Windows only tracks bytes, not packets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TrafficStats {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+/// How well a peer is heard, as `WlanHostedNetworkHelper::client_signal()` would report it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SignalInfo {
+    /// Received signal strength, in dBm.
+    pub rssi_dbm: Option<i32>,
+    /// Link quality from 0 to 100.
+    pub quality: Option<u8>,
+}
+
+/// Adds up the usage Windows has recorded for the adapter's connection profile since `since`, or
+/// returns `None` if no profile belongs to the adapter.
+#[cfg(windows)]
+pub(crate) fn adapter_usage(adapter_id: Guid, since: SystemTime) -> Result<Option<TrafficStats>> {
+    let profiles = NetworkInformation::GetConnectionProfiles()?;
+    for profile in &profiles {
+        // profiles without an adapter, like a dial-up connection, can't be ours
+        let profile_adapter = match profile.NetworkAdapter() {
+            Ok(adapter) => Guid::from(adapter.NetworkAdapterId()?),
+            Err(_) => continue,
+        };
+        if profile_adapter != adapter_id {
+            continue;
+        }
+        let states = NetworkUsageStates {
+            Roaming: TriStates::DoNotCare,
+            Shared: TriStates::DoNotCare,
+        };
+        let usages = profile
+            .GetNetworkUsageAsync(
+                platform::date_time(since),
+                platform::date_time(SystemTime::now()),
+                DataUsageGranularity::Total,
+                states,
+            )?
+            .get()?;
+        let mut stats = TrafficStats::default();
+        for usage in &usages {
+            stats.bytes_sent += usage.BytesSent()?;
+            stats.bytes_received += usage.BytesReceived()?;
+        }
+        return Ok(Some(stats));
+    }
+    Ok(None)
+}
+
+/// Without Windows no adapter is ever known, so there's no usage to look up.
+#[cfg(not(windows))]
+pub(crate) fn adapter_usage(_adapter_id: Guid, _since: SystemTime) -> Result<Option<TrafficStats>> {
+    Ok(None)
+}
@@ -0,0 +1,114 @@
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use crate::HostedNetworkError;
+
+/// State of the hosted network, mirroring `WiFiDirectAdvertisementPublisherStatus`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HostedNetworkStatus {
+    Created,
+    Started,
+    Stopped,
+    Aborted,
+}
+
+/// Why the network went down, as resolved by `WlanHostedNetworkHelper::stopped()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StopReason {
+    /// `stop()` was called, or the helper was dropped.
+    Stopped,
+    /// The builder's `idle_timeout` ran out.
+    Idle,
+    /// Windows tore the network down.
+    Aborted(HostedNetworkError),
+}
+
+impl fmt::Display for HostedNetworkStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Created => write!(f, "Created"),
+            Self::Started => write!(f, "Started"),
+            Self::Stopped => write!(f, "Stopped"),
+            Self::Aborted => write!(f, "Aborted"),
+        }
+    }
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stopped => write!(f, "Stopped"),
+            Self::Idle => write!(f, "Stopped after idling"),
+            Self::Aborted(error) => write!(f, "Aborted: {}", error),
+        }
+    }
+}
+
+/// The publisher's most recent status, and the channels to tell about the next one. Updating and
+/// subscribing both happen under one lock, so a subscriber sees every status from the one current
+/// when it subscribed, with none missed or repeated.
+pub(crate) struct StatusCache {
+    pub(crate) last: HostedNetworkStatus,
+    subscribers: Vec<Sender<HostedNetworkStatus>>,
+}
+
+impl StatusCache {
+    pub(crate) fn new() -> Self {
+        StatusCache {
+            last: HostedNetworkStatus::Created,
+            subscribers: Vec::new(),
+        }
+    }
+
+    /// Records the new status and sends it to every subscriber, forgetting those that hung up.
+    pub(crate) fn update(&mut self, status: HostedNetworkStatus) {
+        self.last = status;
+        self.subscribers
+            .retain(|subscriber| subscriber.send(status).is_ok());
+    }
+
+    /// Returns a channel that receives the current status right away, then each one after it.
+    pub(crate) fn subscribe(&mut self) -> Receiver<HostedNetworkStatus> {
+        let (status_tx, status_rx) = mpsc::channel();
+        let _ = status_tx.send(self.last);
+        self.subscribers.push(status_tx);
+        status_rx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HostedNetworkStatus, StatusCache};
+
+    #[test]
+    fn late_subscribers_get_the_current_status() {
+        let mut cache = StatusCache::new();
+        let early = cache.subscribe();
+        cache.update(HostedNetworkStatus::Started);
+        let late = cache.subscribe();
+        cache.update(HostedNetworkStatus::Stopped);
+        assert_eq!(
+            early.try_iter().collect::<Vec<_>>(),
+            vec![
+                HostedNetworkStatus::Created,
+                HostedNetworkStatus::Started,
+                HostedNetworkStatus::Stopped
+            ]
+        );
+        assert_eq!(
+            late.try_iter().collect::<Vec<_>>(),
+            vec![HostedNetworkStatus::Started, HostedNetworkStatus::Stopped]
+        );
+        assert_eq!(cache.last, HostedNetworkStatus::Stopped);
+    }
+
+    #[test]
+    fn dropped_subscribers_are_forgotten() {
+        let mut cache = StatusCache::new();
+        drop(cache.subscribe());
+        cache.update(HostedNetworkStatus::Started);
+        assert!(cache.subscribers.is_empty());
+    }
+}
@@ -0,0 +1,61 @@
+//! Locking that outlives a panic, and the crate's threads. A thread that panics while holding one
+//! of the helper's mutexes poisons it, and with `lock().expect()` every later call, including ones
+//! made from WinRT callbacks, would panic in turn. The data behind these mutexes stays consistent
+//! between statements, so the guard is taken back instead.
+
+use std::io;
+use std::sync::{Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+/// What the crate's threads are named after unless the builder's `thread_name_prefix()` says
+/// otherwise.
+pub(crate) const DEFAULT_THREAD_NAME_PREFIX: &str = "wifidirect-ap";
+
+/// Locks `mutex`, recovering it if a panic poisoned it. `name` identifies it in the warning,
+/// which is only logged the first time, as the poison is cleared.
+pub(crate) fn lock<'a, T: ?Sized>(mutex: &'a Mutex<T>, name: &str) -> MutexGuard<'a, T> {
+    mutex.lock().unwrap_or_else(|poisoned| {
+        warn!("Recovering {} mutex after a panic while it was held", name);
+        mutex.clear_poison();
+        poisoned.into_inner()
+    })
+}
+
+/// Spawns a thread named `name`, so it can be told apart in debuggers and crash dumps. Fails if
+/// the OS won't start another thread, which callers running in WinRT callbacks must survive, so
+/// this never panics. The error names the thread.
+pub(crate) fn spawn<F, T>(name: String, f: F) -> io::Result<JoinHandle<T>>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    thread::Builder::new()
+        .name(name.clone())
+        .spawn(f)
+        .map_err(|error| io::Error::new(error.kind(), format!("{}: {}", name, error)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lock;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    #[test]
+    fn poisoned_mutex_is_recovered() {
+        let mutex = Arc::new(Mutex::new(1));
+        let poisoner = mutex.clone();
+        let _ = thread::spawn(move || {
+            let mut value = poisoner.lock().unwrap();
+            *value = 2;
+            panic!("poisoning the mutex");
+        })
+        .join();
+        assert!(mutex.is_poisoned());
+        assert_eq!(*lock(&mutex, "test"), 2);
+        // and it stays usable the ordinary way too
+        assert!(!mutex.is_poisoned());
+        *lock(&mutex, "test") = 3;
+        assert_eq!(*mutex.lock().unwrap(), 3);
+    }
+}
@@ -0,0 +1,224 @@
+use crate::builder::InformationElement;
+use crate::HostedNetworkError;
+
+/// 802.11 limit on SSID length, in bytes.
+pub(crate) const MAX_SSID_BYTES: usize = 32;
+/// What the Wi-Fi Direct spec starts every group's SSID with.
+pub(crate) const DIRECT_SSID_PREFIX: &str = "DIRECT-";
+/// WPA2 limits on passphrase length, in characters.
+pub(crate) const MIN_PASSPHRASE_CHARS: usize = 8;
+pub(crate) const MAX_PASSPHRASE_CHARS: usize = 63;
+/// 802.11 limit on an information element's contents, in bytes, of which the OUI and its type
+/// take four.
+pub(crate) const MAX_INFORMATION_ELEMENT_BYTES: usize = 255;
+/// Room for all the vendor-specific elements together, counting the two-byte ID and length header
+/// 802.11 puts in front of each. Beacons share their space with the P2P and WPS elements Windows
+/// adds, and Windows aborts the network rather than send a frame that's too big, so this keeps
+/// well clear of that.
+pub(crate) const MAX_INFORMATION_ELEMENTS_TOTAL_BYTES: usize = 512;
+/// The element ID and length bytes in front of every 802.11 element.
+const INFORMATION_ELEMENT_HEADER_BYTES: usize = 2;
+/// Highest Wi-Fi Direct group owner intent, meaning the device insists on being group owner.
+pub(crate) const MAX_GROUP_OWNER_INTENT: u8 = 15;
+
+/// Checks an SSID before it is handed to Windows, which would otherwise abort the network with an
+/// unhelpful error.
+pub(crate) fn validate_ssid(ssid: &str) -> Result<(), HostedNetworkError> {
+    // the 802.11 limit is on the encoded SSID, so count UTF-8 bytes rather than chars
+    if ssid.is_empty() || ssid.len() > MAX_SSID_BYTES {
+        return Err(HostedNetworkError::InvalidSsid(format!(
+            "must be 1 to {} bytes long, got {}",
+            MAX_SSID_BYTES,
+            ssid.len()
+        )));
+    }
+    // control characters are mangled by WinRT and break QR codes and UIs that show the SSID
+    if let Some(position) = ssid.bytes().position(|byte| byte.is_ascii_control()) {
+        return Err(HostedNetworkError::InvalidSsid(format!(
+            "control character 0x{:02X} at byte {}",
+            ssid.as_bytes()[position],
+            position
+        )));
+    }
+    Ok(())
+}
+
+/// Checks a WPA2 passphrase before it is handed to Windows.
+pub(crate) fn validate_passphrase(passphrase: &str) -> Result<(), HostedNetworkError> {
+    // Wi-Fi Direct groups are always WPA2-Personal, so there's no open mode to fall back to
+    if passphrase.is_empty() {
+        return Err(HostedNetworkError::InvalidPassphrase(
+            "open networks aren't supported, Wi-Fi Direct requires WPA2".to_string(),
+        ));
+    }
+    let len = passphrase.chars().count();
+    if !(MIN_PASSPHRASE_CHARS..=MAX_PASSPHRASE_CHARS).contains(&len) {
+        return Err(HostedNetworkError::InvalidPassphrase(format!(
+            "must be {} to {} characters long, got {}",
+            MIN_PASSPHRASE_CHARS, MAX_PASSPHRASE_CHARS, len
+        )));
+    }
+    Ok(())
+}
+
+/// Checks a vendor-specific information element fits in a single 802.11 element.
+pub(crate) fn validate_information_element(
+    element: &InformationElement,
+) -> Result<(), HostedNetworkError> {
+    let len = element.oui.len() + 1 + element.data.len();
+    if len > MAX_INFORMATION_ELEMENT_BYTES {
+        return Err(HostedNetworkError::InvalidInformationElement(format!(
+            "OUI, type and data must be at most {} bytes, got {}",
+            MAX_INFORMATION_ELEMENT_BYTES, len
+        )));
+    }
+    Ok(())
+}
+
+/// Checks every vendor-specific information element, then that they fit in the advertisement
+/// together.
+pub(crate) fn validate_information_elements(
+    elements: &[InformationElement],
+) -> Result<(), HostedNetworkError> {
+    let mut total = 0;
+    for element in elements {
+        validate_information_element(element)?;
+        total += INFORMATION_ELEMENT_HEADER_BYTES + element.oui.len() + 1 + element.data.len();
+    }
+    if total > MAX_INFORMATION_ELEMENTS_TOTAL_BYTES {
+        return Err(HostedNetworkError::InvalidInformationElement(format!(
+            "{} elements take {} bytes with their headers, at most {} fit",
+            elements.len(),
+            total,
+            MAX_INFORMATION_ELEMENTS_TOTAL_BYTES
+        )));
+    }
+    Ok(())
+}
+
+/// Checks a group owner intent is in range and can take effect, which it only does when the PC
+/// negotiates group ownership instead of being an autonomous group owner.
+pub(crate) fn validate_group_owner_intent(
+    intent: u8,
+    autonomous_group_owner: bool,
+) -> Result<(), HostedNetworkError> {
+    if intent > MAX_GROUP_OWNER_INTENT {
+        return Err(HostedNetworkError::InvalidGroupOwnerIntent(format!(
+            "must be 0 to {}, got {}",
+            MAX_GROUP_OWNER_INTENT, intent
+        )));
+    }
+    if autonomous_group_owner {
+        return Err(HostedNetworkError::InvalidGroupOwnerIntent(
+            "only used in negotiation, so autonomous_group_owner must be off".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        validate_group_owner_intent, validate_information_element, validate_information_elements,
+        validate_passphrase, validate_ssid,
+    };
+    use crate::builder::InformationElement;
+    use crate::HostedNetworkError;
+
+    #[test]
+    fn ssid_length_boundaries() {
+        assert!(validate_ssid("").is_err());
+        assert!(validate_ssid("a").is_ok());
+        assert!(validate_ssid(&"a".repeat(32)).is_ok());
+        assert!(validate_ssid(&"a".repeat(33)).is_err());
+        // 11 three-byte chars is 33 bytes even though it's only 11 chars
+        assert!(validate_ssid(&"€".repeat(10)).is_ok());
+        assert!(validate_ssid(&"€".repeat(11)).is_err());
+    }
+
+    #[test]
+    fn ssid_control_characters_are_rejected() {
+        assert_eq!(
+            validate_ssid("Test\nNetwork"),
+            Err(HostedNetworkError::InvalidSsid(
+                "control character 0x0A at byte 4".to_string()
+            ))
+        );
+        assert_eq!(
+            validate_ssid("\0"),
+            Err(HostedNetworkError::InvalidSsid(
+                "control character 0x00 at byte 0".to_string()
+            ))
+        );
+        assert!(validate_ssid("Test\u{7f}").is_err());
+        // eight four-byte emoji fill the limit exactly, so one more byte goes over
+        assert!(validate_ssid(&"📶".repeat(8)).is_ok());
+        assert!(validate_ssid(&format!("a{}", "📶".repeat(8))).is_err());
+    }
+
+    #[test]
+    fn open_network_is_rejected() {
+        assert_eq!(
+            validate_passphrase(""),
+            Err(HostedNetworkError::InvalidPassphrase(
+                "open networks aren't supported, Wi-Fi Direct requires WPA2".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn passphrase_length_boundaries() {
+        assert!(validate_passphrase(&"a".repeat(7)).is_err());
+        assert!(validate_passphrase(&"a".repeat(8)).is_ok());
+        assert!(validate_passphrase(&"a".repeat(63)).is_ok());
+        assert!(validate_passphrase(&"a".repeat(64)).is_err());
+    }
+
+    #[test]
+    fn information_element_size_boundaries() {
+        let element = |len| InformationElement {
+            oui: [0x00, 0x50, 0xf2],
+            oui_type: 4,
+            data: vec![0; len],
+        };
+        assert!(validate_information_element(&element(251)).is_ok());
+        assert_eq!(
+            validate_information_element(&element(252)),
+            Err(HostedNetworkError::InvalidInformationElement(
+                "OUI, type and data must be at most 255 bytes, got 256".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn information_elements_share_one_budget() {
+        let element = |len| InformationElement {
+            oui: [0x00, 0x50, 0xf2],
+            oui_type: 4,
+            data: vec![0; len],
+        };
+        // two headers, OUIs and types are 12 bytes, leaving 500 for the data
+        assert!(validate_information_elements(&[element(250), element(250)]).is_ok());
+        assert!(validate_information_elements(&[]).is_ok());
+        // each fits on its own, but not all together
+        assert_eq!(
+            validate_information_elements(&vec![element(100); 5]),
+            Err(HostedNetworkError::InvalidInformationElement(
+                "5 elements take 530 bytes with their headers, at most 512 fit".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn group_owner_intent_needs_negotiation() {
+        assert!(validate_group_owner_intent(0, false).is_ok());
+        assert!(validate_group_owner_intent(15, false).is_ok());
+        assert_eq!(
+            validate_group_owner_intent(16, false),
+            Err(HostedNetworkError::InvalidGroupOwnerIntent(
+                "must be 0 to 15, got 16".to_string()
+            ))
+        );
+        assert!(validate_group_owner_intent(7, true).is_err());
+    }
+}
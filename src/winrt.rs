@@ -0,0 +1,26 @@
+//! Escape hatches to the Windows Runtime objects behind a network, for calling methods this crate
+//! doesn't wrap. Only on Windows. The types are the `windows` crate's, re-exported from the version
+//! this crate is built against, so code using them moves with this crate's `windows` upgrades.
+
+pub use crate::platform::WiFiDirectAdvertisementPublisher;
+
+use crate::{sync, WlanHostedNetworkHelper};
+
+impl WlanHostedNetworkHelper {
+    /// Runs `f` on the `WiFiDirectAdvertisementPublisher` behind the network, for calling WinRT
+    /// methods this crate doesn't wrap. Returns `None` with the `mock` feature, which has no real
+    /// publisher.
+    ///
+    /// The helper's lock is held while `f` runs, so `f` must not call back into the helper. The
+    /// helper expects to own the publisher's lifecycle: calling `Start()` or `Stop()` directly,
+    /// or removing its `StatusChanged` handler, leaves the helper's state and events out of step
+    /// with Windows. Use `restart()` and `stop()` instead. The publisher is replaced on every
+    /// restart, so don't keep a clone of it around either.
+    pub fn with_publisher<R>(
+        &self,
+        f: impl FnOnce(&WiFiDirectAdvertisementPublisher) -> R,
+    ) -> Option<R> {
+        let publisher = sync::lock(&self.inner.publisher, "publisher");
+        publisher.winrt().map(f)
+    }
+}